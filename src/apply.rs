@@ -0,0 +1,249 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::filesystem::FileSystem;
+use crate::journal::{FolderAction, Journal};
+use crate::plan::{PlannedRename, SkipReason};
+
+/// What happened to one entry of a plan.
+pub enum Outcome {
+    Renamed,
+    Skipped { code: &'static str, message: String },
+    Failed(String),
+    RolledBack,
+    RollbackFailed(String),
+}
+
+impl Outcome {
+    pub fn skipped(reason: &SkipReason) -> Outcome {
+        Outcome::Skipped {
+            code: reason.code(),
+            message: reason.to_string(),
+        }
+    }
+}
+
+/// What `execute` does when a rename does not succeed.
+#[derive(Clone, Copy)]
+pub enum StopPolicy {
+    Continue,
+    Stop,
+    RollBack,
+}
+
+impl StopPolicy {
+    /// The skipped rows that keep a run from starting at all: the first one
+    /// when stopping, every one when rolling back, and none otherwise. Rows
+    /// that ask for no rename never do.
+    pub fn rejected(self, plan: &[PlannedRename]) -> Vec<&PlannedRename> {
+        let mut skipped = plan
+            .iter()
+            .filter(|rename| rename.skip.is_some() && rename.is_requested());
+        match self {
+            StopPolicy::Continue => Vec::new(),
+            StopPolicy::Stop => skipped.next().into_iter().collect(),
+            StopPolicy::RollBack => skipped.collect(),
+        }
+    }
+}
+
+/// How a run ended, beyond the outcome of each entry.
+#[derive(Default)]
+pub struct Execution {
+    /// The row that ended the run early, if `StopPolicy` stopped it.
+    pub stopped_at: Option<usize>,
+    pub rollback: Option<Rollback>,
+    /// The positions in the plan of the renames that ran, in the order they
+    /// ran. None are left after a rollback, even those it could not reverse.
+    pub renamed: Vec<usize>,
+}
+
+pub struct Rollback {
+    pub reversed: usize,
+    /// Reverse renames that could not be performed.
+    pub stuck: Vec<PlannedRename>,
+}
+
+/// Performs every rename in the plan that passed its checks, recording each
+/// outcome in the journal and passing it to `on_outcome` as it happens.
+///
+/// Missing folders a rename moves into are created first if the rename
+/// allows it, and recorded in the journal.
+///
+/// Unless `stop` is `Continue`, the first rename that does not succeed ends
+/// the run, rows that ask for no rename aside; with `RollBack` every rename
+/// already performed is then reversed, newest first, and the folders created
+/// for it are removed. Only journal failures are returned as errors.
+pub fn execute(
+    fs: &impl FileSystem,
+    plan: &[PlannedRename],
+    mut journal: Option<&mut Journal>,
+    stop: StopPolicy,
+    mut on_outcome: impl FnMut(&PlannedRename, &Outcome),
+) -> Result<Execution, Error> {
+    let mut execution = Execution::default();
+    let mut performed = Vec::new();
+
+    for (index, rename) in plan.iter().enumerate() {
+        let (outcome, created) = apply_rename(fs, rename);
+        if let Some(journal) = journal.as_deref_mut() {
+            for folder in &created {
+                journal.record_folder(Some(rename.row), FolderAction::Created, folder)?;
+            }
+            journal.record(rename, &outcome)?;
+        }
+        on_outcome(rename, &outcome);
+
+        if let Outcome::Renamed = outcome {
+            execution.renamed.push(index);
+            performed.push((rename, created));
+            continue;
+        }
+        if rename.skip.is_some() && !rename.is_requested() {
+            continue;
+        }
+
+        match stop {
+            StopPolicy::Continue => {}
+            StopPolicy::Stop => {
+                execution.stopped_at = Some(rename.row);
+                break;
+            }
+            StopPolicy::RollBack => {
+                execution.stopped_at = Some(rename.row);
+                execution.rollback = Some(roll_back(fs, &performed, journal, &mut on_outcome)?);
+                execution.renamed.clear();
+                break;
+            }
+        }
+    }
+
+    Ok(execution)
+}
+
+fn roll_back(
+    fs: &impl FileSystem,
+    performed: &[(&PlannedRename, Vec<PathBuf>)],
+    mut journal: Option<&mut Journal>,
+    on_outcome: &mut impl FnMut(&PlannedRename, &Outcome),
+) -> Result<Rollback, Error> {
+    let mut rollback = Rollback {
+        reversed: 0,
+        stuck: Vec::new(),
+    };
+
+    for (rename, created) in performed.iter().rev() {
+        let reverse = rename.reversed();
+
+        let outcome = if fs.exists(&reverse.new_path) {
+            Outcome::RollbackFailed("target already exists".to_string())
+        } else {
+            match fs.rename(&reverse.old_path, &reverse.new_path) {
+                Ok(()) => Outcome::RolledBack,
+                Err(error) => Outcome::RollbackFailed(error.to_string()),
+            }
+        };
+
+        if let Some(journal) = journal.as_deref_mut() {
+            journal.record(&reverse, &outcome)?;
+        }
+        on_outcome(&reverse, &outcome);
+
+        match outcome {
+            Outcome::RolledBack => rollback.reversed += 1,
+            _ => rollback.stuck.push(reverse),
+        }
+
+        for folder in remove_folders(fs, created) {
+            if let Some(journal) = journal.as_deref_mut() {
+                journal.record_folder(Some(rename.row), FolderAction::Removed, &folder)?;
+            }
+        }
+    }
+
+    Ok(rollback)
+}
+
+/// Removes each of `folders` that is empty, in order, and records it in the
+/// journal. List nested folders before their parents.
+pub fn remove_empty_folders(
+    fs: &impl FileSystem,
+    folders: &[PathBuf],
+    mut journal: Option<&mut Journal>,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut removed = Vec::new();
+
+    for folder in folders {
+        if fs.remove_dir(folder).is_ok() {
+            if let Some(journal) = journal.as_deref_mut() {
+                journal.record_folder(None, FolderAction::Removed, folder)?;
+            }
+            removed.push(folder.clone());
+        }
+    }
+
+    Ok(removed)
+}
+
+fn apply_rename(fs: &impl FileSystem, rename: &PlannedRename) -> (Outcome, Vec<PathBuf>) {
+    if let Some(reason) = &rename.skip {
+        return (Outcome::skipped(reason), Vec::new());
+    }
+
+    // An earlier row that was planned to free this name may have failed.
+    if fs.exists(&rename.new_path) {
+        let reason = SkipReason::TargetExists(rename.new_path.clone());
+        return (Outcome::skipped(&reason), Vec::new());
+    }
+
+    let created = match rename.new_path.parent() {
+        Some(parent) if rename.create_parents => match create_folders(fs, parent) {
+            Ok(created) => created,
+            Err(error) => {
+                let message = format!("failed to create {}: {error}", parent.display());
+                return (Outcome::Failed(message), Vec::new());
+            }
+        },
+        _ => Vec::new(),
+    };
+
+    match fs.rename(&rename.old_path, &rename.new_path) {
+        Ok(()) => (Outcome::Renamed, created),
+        Err(error) => {
+            remove_folders(fs, &created);
+            (Outcome::Failed(error.to_string()), Vec::new())
+        }
+    }
+}
+
+/// Creates `folder` and whichever of its parents are missing, and returns
+/// the folders it created, outermost first.
+fn create_folders(fs: &impl FileSystem, folder: &Path) -> io::Result<Vec<PathBuf>> {
+    let missing: Vec<&Path> = folder
+        .ancestors()
+        .take_while(|ancestor| !fs.exists(ancestor))
+        .collect();
+    let mut created = Vec::new();
+
+    for folder in missing.into_iter().rev() {
+        if let Err(error) = fs.create_dir(folder) {
+            remove_folders(fs, &created);
+            return Err(error);
+        }
+        created.push(folder.to_path_buf());
+    }
+
+    Ok(created)
+}
+
+/// Removes folders `create_folders` created, innermost first, as long as
+/// they are still empty, and returns the ones it removed.
+fn remove_folders(fs: &impl FileSystem, created: &[PathBuf]) -> Vec<PathBuf> {
+    created
+        .iter()
+        .rev()
+        .filter(|folder| fs.remove_dir(folder).is_ok())
+        .cloned()
+        .collect()
+}
@@ -0,0 +1,197 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::datetime::format_timestamp;
+use crate::entry::{Contents, Context};
+use crate::filesystem::FileSystem;
+
+/// A read-only column `export --columns` adds after the names, to help
+/// choose new names in a spreadsheet. Import ignores them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Column {
+    Modified,
+    Created,
+    /// The size of a file, or of everything inside a folder, in bytes.
+    Size,
+    /// The number of entries inside a folder, at any depth.
+    Items,
+    Owner,
+    Group,
+    Permissions,
+}
+
+impl Column {
+    pub const ALL: [Column; 7] = [
+        Column::Modified,
+        Column::Created,
+        Column::Size,
+        Column::Items,
+        Column::Owner,
+        Column::Group,
+        Column::Permissions,
+    ];
+
+    /// The column's header, which is also how `--columns` names it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Column::Modified => "modified",
+            Column::Created => "created",
+            Column::Size => "size",
+            Column::Items => "items",
+            Column::Owner => "owner",
+            Column::Group => "group",
+            Column::Permissions => "permissions",
+        }
+    }
+}
+
+impl FromStr for Column {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Column, ()> {
+        Column::ALL
+            .into_iter()
+            .find(|column| column.as_str() == value)
+            .ok_or(())
+    }
+}
+
+/// A cell of a metadata column. Values that cannot be read are `Empty`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Value {
+    Empty,
+    Text(String),
+    Number(u64),
+    Time(SystemTime),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Text(text) => f.write_str(text),
+            Value::Number(number) => write!(f, "{number}"),
+            Value::Time(time) => f.write_str(&format_timestamp(*time)),
+        }
+    }
+}
+
+/// The metadata columns chosen for an export, with the user and group names
+/// their owners are shown by, and the folders measured for their values so
+/// far.
+#[derive(Default)]
+pub struct Columns {
+    columns: Vec<Column>,
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+    contents: RefCell<Contents>,
+}
+
+impl Columns {
+    /// Looks up user and group names only if a column needs them.
+    pub fn new(columns: Vec<Column>) -> Columns {
+        let users = if columns.contains(&Column::Owner) {
+            account_names("/etc/passwd")
+        } else {
+            HashMap::new()
+        };
+        let groups = if columns.contains(&Column::Group) {
+            account_names("/etc/group")
+        } else {
+            HashMap::new()
+        };
+
+        Columns {
+            columns,
+            users,
+            groups,
+            contents: RefCell::default(),
+        }
+    }
+
+    pub fn headers(&self) -> Vec<&'static str> {
+        self.columns.iter().map(|column| column.as_str()).collect()
+    }
+
+    /// The values of every column for the entry at `path`. Symlinked
+    /// folders are not followed. Folders are only read the first time they
+    /// are measured, so values of a folder listed after its parent, as
+    /// export lists them, cost nothing extra.
+    pub fn values(&self, fs: &impl FileSystem, path: &Path) -> Vec<Value> {
+        let metadata = fs.metadata(path).ok();
+        let needs_contents = self
+            .columns
+            .iter()
+            .any(|column| matches!(column, Column::Size | Column::Items));
+        let context = needs_contents
+            .then(|| Context::measure(fs, path, &mut self.contents.borrow_mut()))
+            .flatten();
+
+        self.columns
+            .iter()
+            .map(|column| {
+                let value = match column {
+                    Column::Modified => metadata
+                        .and_then(|metadata| metadata.modified)
+                        .map(Value::Time),
+                    Column::Created => metadata
+                        .and_then(|metadata| metadata.created)
+                        .map(Value::Time),
+                    Column::Size => context.as_ref().map(|context| Value::Number(context.size)),
+                    Column::Items => context
+                        .as_ref()
+                        .and_then(|context| context.items)
+                        .map(|items| Value::Number(items as u64)),
+                    Column::Owner => metadata
+                        .and_then(|metadata| metadata.owner)
+                        .map(|owner| account_name(&self.users, owner)),
+                    Column::Group => metadata
+                        .and_then(|metadata| metadata.group)
+                        .map(|group| account_name(&self.groups, group)),
+                    Column::Permissions => metadata
+                        .and_then(|metadata| metadata.mode)
+                        .map(|mode| Value::Text(permissions(mode))),
+                };
+                value.unwrap_or(Value::Empty)
+            })
+            .collect()
+    }
+}
+
+/// The names in `/etc/passwd` or `/etc/group` by id. Ids without a name,
+/// or a system without these files, show as numbers instead.
+fn account_names(path: &str) -> HashMap<u32, String> {
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((id, name.to_string()))
+        })
+        .collect()
+}
+
+fn account_name(names: &HashMap<u32, String>, id: u32) -> Value {
+    Value::Text(names.get(&id).cloned().unwrap_or_else(|| id.to_string()))
+}
+
+/// Unix permission bits as `ls` shows them, e.g. `rwxr-x---`.
+fn permissions(mode: u32) -> String {
+    let mut text = String::with_capacity(9);
+
+    for shift in [6, 3, 0] {
+        let bits = mode >> shift;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+
+    text
+}
@@ -0,0 +1,88 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A point in time broken down into UTC calendar fields.
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u64,
+    pub minute: u64,
+    pub second: u64,
+}
+
+impl DateTime {
+    pub fn from_system_time(time: SystemTime) -> DateTime {
+        let seconds = time
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+        let time_of_day = seconds % 86_400;
+
+        DateTime {
+            year,
+            month,
+            day,
+            hour: time_of_day / 3600,
+            minute: time_of_day % 3600 / 60,
+            second: time_of_day % 60,
+        }
+    }
+
+    /// Formats with a small strftime subset: `%Y`, `%m`, `%d`, `%H`, `%M`,
+    /// `%S` and `%%`. Anything else is copied as is.
+    pub fn format(&self, pattern: &str) -> String {
+        let mut formatted = String::new();
+        let mut chars = pattern.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                formatted.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('Y') => formatted.push_str(&format!("{:04}", self.year)),
+                Some('m') => formatted.push_str(&format!("{:02}", self.month)),
+                Some('d') => formatted.push_str(&format!("{:02}", self.day)),
+                Some('H') => formatted.push_str(&format!("{:02}", self.hour)),
+                Some('M') => formatted.push_str(&format!("{:02}", self.minute)),
+                Some('S') => formatted.push_str(&format!("{:02}", self.second)),
+                Some('%') => formatted.push('%'),
+                Some(other) => {
+                    formatted.push('%');
+                    formatted.push(other);
+                }
+                None => formatted.push('%'),
+            }
+        }
+
+        formatted
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp, e.g. `2024-03-09T14:05:00Z`.
+pub fn format_timestamp(time: SystemTime) -> String {
+    DateTime::from_system_time(time).format("%Y-%m-%dT%H:%M:%SZ")
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) civil date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
@@ -0,0 +1,206 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// How a CSV is written: its delimiter and quote character, whether it
+/// starts with a UTF-8 byte order mark, and whether lines end in `\r\n`.
+#[derive(Clone, Copy)]
+pub struct Dialect {
+    pub delimiter: u8,
+    pub quote: u8,
+    pub bom: bool,
+    pub crlf: bool,
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect {
+            delimiter: b',',
+            quote: b'"',
+            bom: false,
+            crlf: false,
+        }
+    }
+}
+
+impl Dialect {
+    /// What Excel opens with a double-click: a byte order mark, so names are
+    /// read as UTF-8, the list separator of the user's locale, and `\r\n`.
+    pub fn excel() -> Dialect {
+        Dialect {
+            delimiter: locale_delimiter(),
+            quote: b'"',
+            bom: true,
+            crlf: true,
+        }
+    }
+
+    /// Creates the file at `path` and a CSV writer for it.
+    pub fn create(&self, path: &Path) -> io::Result<csv::Writer<File>> {
+        let mut file = File::create(path)?;
+        if self.bom {
+            file.write_all(BOM)?;
+        }
+
+        let terminator = if self.crlf {
+            csv::Terminator::CRLF
+        } else {
+            csv::Terminator::Any(b'\n')
+        };
+        Ok(csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .terminator(terminator)
+            .from_writer(file))
+    }
+}
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// Languages that write decimals with a comma, for which spreadsheets use
+/// `;` to separate values instead.
+const DECIMAL_COMMA: [&str; 48] = [
+    "af", "az", "be", "bg", "bs", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fo", "fr",
+    "fy", "gl", "hr", "hu", "hy", "id", "is", "it", "ka", "kk", "ky", "lb", "lt", "lv", "mk", "mn",
+    "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sq", "sr", "sv", "tr", "uk", "vi",
+];
+
+/// Regions where those languages write decimals with a point after all.
+const DECIMAL_POINT_REGIONS: [&str; 13] = [
+    "de_CH", "de_LI", "fr_CH", "it_CH", "es_DO", "es_GT", "es_HN", "es_MX", "es_NI", "es_PA",
+    "es_PR", "es_SV", "es_US",
+];
+
+/// The delimiter a spreadsheet expects under the locale in `LC_ALL`,
+/// `LC_NUMERIC` or `LANG`: `;` where decimals are written with a comma,
+/// `,` everywhere else. Spreadsheets can be set up otherwise, so this is a
+/// best guess.
+pub fn locale_delimiter() -> u8 {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .into_iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let mut parts = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or("")
+        .split(['_', '-']);
+    let language = parts.next().unwrap_or("").to_lowercase();
+    let region = parts.next().unwrap_or("").to_uppercase();
+
+    if DECIMAL_COMMA.contains(&language.as_str())
+        && !DECIMAL_POINT_REGIONS.contains(&format!("{language}_{region}").as_str())
+    {
+        b';'
+    } else {
+        b','
+    }
+}
+
+/// The text encodings `decode` recognizes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+/// Decodes a CSV as UTF-8 or UTF-16 if it starts with their byte order
+/// mark, which is dropped, or looks like UTF-16 without one. Anything else
+/// is UTF-8 if it is valid UTF-8, and Windows-1252 otherwise.
+pub fn decode(bytes: &[u8]) -> (String, Encoding) {
+    if let Some(rest) = bytes.strip_prefix(BOM) {
+        return (String::from_utf8_lossy(rest).into_owned(), Encoding::Utf8);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xff\xfe") {
+        return (decode_utf16(rest, u16::from_le_bytes), Encoding::Utf16Le);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xfe\xff") {
+        return (decode_utf16(rest, u16::from_be_bytes), Encoding::Utf16Be);
+    }
+
+    // ASCII text in UTF-16 has a zero in every other byte.
+    let zeros_at = |offset: usize| {
+        bytes
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .take(64)
+            .filter(|&&byte| byte == 0)
+            .count()
+    };
+    if bytes.len() >= 4 {
+        let pairs = (bytes.len() / 2).min(64);
+        if zeros_at(1) * 2 > pairs && zeros_at(0) == 0 {
+            return (decode_utf16(bytes, u16::from_le_bytes), Encoding::Utf16Le);
+        }
+        if zeros_at(0) * 2 > pairs && zeros_at(1) == 0 {
+            return (decode_utf16(bytes, u16::from_be_bytes), Encoding::Utf16Be);
+        }
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (text.to_string(), Encoding::Utf8),
+        Err(_) => (
+            bytes.iter().map(|&byte| windows_1252(byte)).collect(),
+            Encoding::Windows1252,
+        ),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// The characters Windows-1252 puts at 0x80 to 0x9F, where Latin-1 has
+/// control characters. Its other bytes are the Latin-1 code points.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => WINDOWS_1252[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
+
+/// The delimiters `detect_delimiter` chooses from.
+const DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// Guesses the delimiter from the first line of a CSV: whichever of `,`,
+/// `;`, tab and `|` it has most of outside quotes, or `,` if it has none.
+pub fn detect_delimiter(text: &str, quote: u8) -> u8 {
+    let mut counts = [0; DELIMITERS.len()];
+    let mut quoted = false;
+
+    for byte in text.bytes() {
+        if byte == quote {
+            quoted = !quoted;
+        } else if !quoted && (byte == b'\n' || byte == b'\r') {
+            break;
+        } else if let Some(index) = DELIMITERS
+            .iter()
+            .position(|&delimiter| delimiter == byte && !quoted)
+        {
+            counts[index] += 1;
+        }
+    }
+
+    // The first of the most frequent, so ties go to `,`.
+    let (index, &count) = counts
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|&(_, count)| count)
+        .unwrap_or((0, &0));
+    if count == 0 { b',' } else { DELIMITERS[index] }
+}
@@ -0,0 +1,148 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use crate::Error;
+use crate::export::ListedEntry;
+
+const INSTRUCTIONS: &str = "\
+# Edit the names after the numbers, then save and quit.
+# Do not add, remove or reorder lines, or change the numbers.
+# Lines starting with # are ignored.
+";
+
+/// Lets the user rename listed entries by editing their names in `$VISUAL`
+/// or `$EDITOR`. Returns the entries whose name changed, numbered from 1 in
+/// listing order, with `new_name` set.
+pub fn edit_names(listed: Vec<ListedEntry>) -> Result<Vec<(usize, ListedEntry)>, Error> {
+    if let Some(entry) = listed
+        .iter()
+        .find(|entry| entry.name.contains(['\n', '\r']))
+    {
+        return Err(Error::Editor(format!(
+            "Cannot edit names containing line breaks: {:?}",
+            entry.name
+        )));
+    }
+
+    let edit_path = env::temp_dir().join(format!("rename_tool_edit_{}.txt", std::process::id()));
+    write_edit_file(&edit_path, &listed)?;
+
+    let edited = run_editor(&edit_path).and_then(|()| {
+        fs::read_to_string(&edit_path).map_err(|error| {
+            Error::Editor(format!("Failed to read {}: {error}", edit_path.display()))
+        })
+    });
+    let _ = fs::remove_file(&edit_path);
+    let edited = edited?;
+
+    let new_names = parse_edit_file(&edited, listed.len()).map_err(Error::InvalidEdit)?;
+
+    Ok(listed
+        .into_iter()
+        .zip(new_names)
+        .enumerate()
+        .filter(|(_, (entry, new_name))| *new_name != entry.name)
+        .map(|(index, (entry, new_name))| {
+            (
+                index + 1,
+                ListedEntry {
+                    new_name: new_name.to_string(),
+                    ..entry
+                },
+            )
+        })
+        .collect())
+}
+
+fn write_edit_file(edit_path: &Path, listed: &[ListedEntry]) -> Result<(), Error> {
+    let width = listed.len().to_string().len();
+    let mut contents = String::from(INSTRUCTIONS);
+
+    for (index, entry) in listed.iter().enumerate() {
+        contents.push_str(&format!("{:0width$} {}\n", index + 1, entry.name));
+    }
+
+    File::create_new(edit_path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|error| {
+            Error::Editor(format!("Failed to create {}: {error}", edit_path.display()))
+        })
+}
+
+/// Opens the file in the user's editor and waits for it to exit. The editor
+/// setting may include arguments, e.g. `code --wait`.
+fn run_editor(edit_path: &Path) -> Result<(), Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let mut words = editor.split_whitespace();
+    let Some(program) = words.next() else {
+        return Err(Error::Editor("$EDITOR is empty".to_string()));
+    };
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(edit_path)
+        .status()
+        .map_err(|error| Error::Editor(format!("Failed to start editor {editor:?}: {error}")))?;
+
+    if !status.success() {
+        return Err(Error::Editor(format!(
+            "Editor {editor:?} exited with {status}, nothing was renamed."
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reads the edited names back, in listing order, refusing any edit that
+/// added, removed or reordered lines. A name is everything after the first
+/// space, exactly as typed, so leading and trailing spaces are kept.
+pub fn parse_edit_file(edited: &str, expected: usize) -> Result<Vec<&str>, String> {
+    let mut new_names = Vec::with_capacity(expected);
+
+    for (index, line) in edited.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        let (number, new_name) = line.split_once(' ').unwrap_or((line, ""));
+
+        let Ok(number) = number.parse::<usize>() else {
+            return Err(format!(
+                "Line {} has no entry number, lines must not be added: {line:?}",
+                index + 1
+            ));
+        };
+
+        let next = new_names.len() + 1;
+        if number > expected {
+            return Err(format!(
+                "Line {} refers to unknown entry {number}",
+                index + 1
+            ));
+        }
+        if number != next {
+            return Err(if number < next {
+                format!("Line {} repeats or moves entry {number}", index + 1)
+            } else {
+                format!("Entry {next} is missing, lines must not be removed or reordered")
+            });
+        }
+
+        new_names.push(new_name);
+    }
+
+    if new_names.len() != expected {
+        return Err(format!(
+            "Entry {} is missing, lines must not be removed",
+            new_names.len() + 1
+        ));
+    }
+
+    Ok(new_names)
+}
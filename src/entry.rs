@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::datetime::format_timestamp;
+use crate::filesystem::FileSystem;
+
+/// The kind of object an exported name refers to, as recorded in the CSV
+/// `type` column.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Dir,
+    File,
+}
+
+impl EntryKind {
+    /// The kind of whatever is at `path`, following symlinks, or `None` if
+    /// nothing is there.
+    pub fn of(path: &Path) -> Option<EntryKind> {
+        if path.is_dir() {
+            Some(EntryKind::Dir)
+        } else if path.exists() {
+            Some(EntryKind::File)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Dir => "dir",
+            EntryKind::File => "file",
+        }
+    }
+}
+
+impl fmt::Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EntryKind {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<EntryKind, ()> {
+        match value {
+            "dir" => Ok(EntryKind::Dir),
+            "file" => Ok(EntryKind::File),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Which kinds of entries `--type` lets export list and import rename.
+#[derive(Clone, Copy, Default)]
+pub enum TypeFilter {
+    #[default]
+    Dirs,
+    Files,
+    All,
+}
+
+impl TypeFilter {
+    pub fn accepts(self, kind: EntryKind) -> bool {
+        match self {
+            TypeFilter::Dirs => kind == EntryKind::Dir,
+            TypeFilter::Files => kind == EntryKind::File,
+            TypeFilter::All => true,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TypeFilter::Dirs => "dirs",
+            TypeFilter::Files => "files",
+            TypeFilter::All => "all",
+        }
+    }
+}
+
+impl FromStr for TypeFilter {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<TypeFilter, ()> {
+        match value {
+            "dirs" => Ok(TypeFilter::Dirs),
+            "files" => Ok(TypeFilter::Files),
+            "all" => Ok(TypeFilter::All),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What is shown about an entry before deciding on it. `items` counts
+/// everything inside a folder, at any depth, and `size` adds up their sizes.
+pub struct Context {
+    pub kind: EntryKind,
+    pub size: u64,
+    pub items: Option<usize>,
+    pub modified: Option<SystemTime>,
+}
+
+impl Context {
+    /// Looks at the entry at `path`, or returns `None` if it is gone.
+    /// Symlinked folders are not followed: they have no item count and
+    /// the size of the link itself.
+    pub fn of(fs: &impl FileSystem, path: &Path) -> Option<Context> {
+        Context::measure(fs, path, &mut Contents::default())
+    }
+
+    /// Like `of`, but looks up folders `contents` already measured.
+    pub fn measure(fs: &impl FileSystem, path: &Path, contents: &mut Contents) -> Option<Context> {
+        let kind = fs.kind(path)?;
+        let metadata = fs.metadata(path).ok()?;
+
+        let (items, size) = match kind {
+            EntryKind::Dir if !metadata.is_symlink => {
+                let (items, size) = contents.of(fs, path);
+                (Some(items), size)
+            }
+            _ => (None, metadata.size),
+        };
+
+        Some(Context {
+            kind,
+            size,
+            items,
+            modified: metadata.modified,
+        })
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(items) = self.items {
+            write!(f, ", {items} item(s)")?;
+        }
+        write!(f, ", {}", format_size(self.size))?;
+        if let Some(modified) = self.modified {
+            write!(f, ", modified {}", format_timestamp(modified))?;
+        }
+        Ok(())
+    }
+}
+
+/// The number of entries inside folders, at any depth, and the sum of their
+/// sizes, for every folder measured so far. Measuring a folder measures the
+/// folders inside it too, so each is read only once.
+#[derive(Default)]
+pub struct Contents {
+    folders: HashMap<PathBuf, (usize, u64)>,
+}
+
+impl Contents {
+    pub fn of(&mut self, fs: &impl FileSystem, folder: &Path) -> (usize, u64) {
+        if let Some(&contents) = self.folders.get(folder) {
+            return contents;
+        }
+
+        let mut items = 0;
+        let mut size = 0;
+
+        for entry in fs.read_dir(folder).unwrap_or_default() {
+            items += 1;
+
+            if entry.kind == Some(EntryKind::Dir) && !entry.is_symlink {
+                let (nested_items, nested_size) = self.of(fs, &entry.path);
+                items += nested_items;
+                size += nested_size;
+            } else if let Ok(metadata) = fs.metadata(&entry.path) {
+                size += metadata.size;
+            }
+        }
+
+        self.folders.insert(folder.to_path_buf(), (items, size));
+        (items, size)
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can stop an export, import or undo as a whole. Problems
+/// with single rows are reported per row instead, see `plan::SkipReason`.
+#[derive(Debug)]
+pub enum Error {
+    CurrentDir(io::Error),
+    NotADirectory(PathBuf),
+    NotACsvFile(PathBuf),
+    NotAJournalFile(PathBuf),
+    ReadDir {
+        path: PathBuf,
+        source: io::Error,
+    },
+    CreateCsv {
+        path: PathBuf,
+        source: csv::Error,
+    },
+    WriteCsv {
+        path: PathBuf,
+        source: csv::Error,
+    },
+    ReadCsv {
+        path: PathBuf,
+        source: csv::Error,
+    },
+    WriteSpreadsheet {
+        path: PathBuf,
+        message: String,
+    },
+    ReadSpreadsheet {
+        path: PathBuf,
+        message: String,
+    },
+    InvalidHeaders {
+        path: PathBuf,
+        expected: String,
+    },
+    InvalidFingerprint {
+        path: PathBuf,
+        message: String,
+    },
+    CreateJournal {
+        path: PathBuf,
+        source: io::Error,
+    },
+    WriteJournal {
+        path: PathBuf,
+        source: csv::Error,
+    },
+    ReadJournal {
+        path: PathBuf,
+        source: csv::Error,
+    },
+    InvalidJournal {
+        path: PathBuf,
+        message: String,
+    },
+    WriteReport {
+        path: PathBuf,
+        source: io::Error,
+    },
+    InvalidTemplate {
+        template: String,
+        message: String,
+    },
+    InvalidPattern {
+        pattern: String,
+        source: regex::Error,
+    },
+    Editor(String),
+    InvalidEdit(String),
+    Review(io::Error),
+    Terminal(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CurrentDir(error) => write!(f, "Failed to get current directory: {error}"),
+            Error::NotADirectory(path) => write!(f, "Not a valid directory: {}", path.display()),
+            Error::NotACsvFile(path) => write!(f, "Not a valid CSV file: {}", path.display()),
+            Error::NotAJournalFile(path) => {
+                write!(f, "Not a valid journal file: {}", path.display())
+            }
+            Error::ReadDir { path, source } => {
+                write!(f, "Failed to read directory {}: {source}", path.display())
+            }
+            Error::CreateCsv { path, source } => {
+                write!(f, "Failed to create CSV {}: {source}", path.display())
+            }
+            Error::WriteCsv { path, source } => {
+                write!(f, "Failed to write CSV {}: {source}", path.display())
+            }
+            Error::ReadCsv { path, source } => {
+                write!(f, "Failed to read CSV {}: {source}", path.display())
+            }
+            Error::WriteSpreadsheet { path, message } => {
+                write!(
+                    f,
+                    "Failed to write spreadsheet {}: {message}",
+                    path.display()
+                )
+            }
+            Error::ReadSpreadsheet { path, message } => {
+                write!(
+                    f,
+                    "Failed to read spreadsheet {}: {message}",
+                    path.display()
+                )
+            }
+            Error::InvalidHeaders { path, expected } => write!(
+                f,
+                "Invalid CSV headers in {}. Expected: {expected}",
+                path.display()
+            ),
+            Error::InvalidFingerprint { path, message } => {
+                write!(
+                    f,
+                    "Invalid fingerprint column in {}: {message}",
+                    path.display()
+                )
+            }
+            Error::CreateJournal { path, source } => {
+                write!(f, "Failed to create journal {}: {source}", path.display())
+            }
+            Error::WriteJournal { path, source } => {
+                write!(f, "Failed to write journal {}: {source}", path.display())
+            }
+            Error::ReadJournal { path, source } => {
+                write!(f, "Failed to read journal {}: {source}", path.display())
+            }
+            Error::InvalidJournal { path, message } => {
+                write!(f, "Invalid journal {}: {message}", path.display())
+            }
+            Error::WriteReport { path, source } => {
+                write!(f, "Failed to write report {}: {source}", path.display())
+            }
+            Error::InvalidTemplate { template, message } => {
+                write!(f, "Invalid template {template:?}: {message}")
+            }
+            Error::InvalidPattern { pattern, source } => {
+                write!(f, "Invalid pattern {pattern:?}: {source}")
+            }
+            Error::Editor(message) | Error::InvalidEdit(message) => f.write_str(message),
+            Error::Review(error) => write!(f, "Interactive review failed: {error}"),
+            Error::Terminal(error) => write!(f, "Terminal UI failed: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CurrentDir(source)
+            | Error::Review(source)
+            | Error::Terminal(source)
+            | Error::ReadDir { source, .. }
+            | Error::CreateJournal { source, .. }
+            | Error::WriteReport { source, .. } => Some(source),
+            Error::CreateCsv { source, .. }
+            | Error::WriteCsv { source, .. }
+            | Error::ReadCsv { source, .. }
+            | Error::WriteJournal { source, .. }
+            | Error::ReadJournal { source, .. } => Some(source),
+            Error::InvalidPattern { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
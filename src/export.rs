@@ -0,0 +1,288 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::columns::{Columns, Value};
+use crate::dialect::Dialect;
+use crate::entry::{EntryKind, TypeFilter};
+use crate::filesystem::{DirEntry, FileSystem};
+use crate::fingerprint::{Fingerprint, Stamp};
+use crate::rawname;
+use crate::spreadsheet;
+use crate::template::Template;
+
+/// Which entries a directory scan lists. `max_depth` counts the directory's
+/// own children as depth 1; without `recursive` or `max_depth` only that
+/// level is listed.
+#[derive(Default)]
+pub struct ExportOptions {
+    pub recursive: bool,
+    pub max_depth: Option<usize>,
+    pub type_filter: TypeFilter,
+}
+
+/// An entry found by the directory scan, with the name it should get.
+#[derive(Clone)]
+pub struct ListedEntry {
+    /// The `/`-separated path relative to the scanned directory. Parts that
+    /// are not valid UTF-8 are replaced by U+FFFD.
+    pub name: String,
+    /// The escaped form of `name` if it is not valid UTF-8, see `rawname`.
+    pub raw_name: Option<String>,
+    pub new_name: String,
+    pub kind: EntryKind,
+    pub path: PathBuf,
+    /// `None` if the entry's metadata could not be read.
+    pub fingerprint: Option<Fingerprint>,
+}
+
+/// The result of a directory scan. Nested folders that could not be read
+/// are skipped and listed in `unreadable`.
+pub struct Listing {
+    pub entries: Vec<ListedEntry>,
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+    pub stamp: Stamp,
+}
+
+impl Listing {
+    /// Leaves an entry out of the listing and its stamp, such as the CSV the
+    /// listing is written to.
+    pub fn exclude(&mut self, path: &Path) {
+        self.entries.retain(|entry| entry.path != path);
+        self.stamp.rehash(&self.entries);
+    }
+}
+
+/// Scans the directory the way `export` does.
+pub fn list_entries(
+    fs: &impl FileSystem,
+    directory: &Path,
+    options: &ExportOptions,
+) -> Result<Listing, Error> {
+    if !fs.is_dir(directory) {
+        return Err(Error::NotADirectory(directory.to_path_buf()));
+    }
+
+    let entries = fs.read_dir(directory).map_err(|source| Error::ReadDir {
+        path: directory.to_path_buf(),
+        source,
+    })?;
+
+    let max_depth = match options.max_depth {
+        Some(max_depth) => max_depth,
+        None if options.recursive => usize::MAX,
+        None => 1,
+    };
+
+    let mut listing = Listing {
+        entries: Vec::new(),
+        unreadable: Vec::new(),
+        stamp: Stamp::new(options, &[]),
+    };
+    collect_entries(
+        fs,
+        entries,
+        Path::new(""),
+        1,
+        max_depth,
+        options.type_filter,
+        &mut listing,
+    );
+    listing.stamp = Stamp::new(options, &listing.entries);
+
+    Ok(listing)
+}
+
+/// Fills in `new_name` for every entry from the template. Entries the
+/// template cannot be rendered for keep an empty `new_name` and are returned
+/// with the reason.
+pub fn apply_template(
+    fs: &impl FileSystem,
+    template: &Template,
+    directory: &Path,
+    listed: &mut [ListedEntry],
+) -> Vec<(String, String)> {
+    let root_name = directory
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut failures = Vec::new();
+
+    for (index, entry) in listed.iter_mut().enumerate() {
+        let (parent_path, parent) = match entry.name.rsplit_once('/') {
+            Some((parent_path, _)) => (
+                Some(parent_path),
+                parent_path.rsplit('/').next().unwrap_or(parent_path),
+            ),
+            None => (None, root_name.as_str()),
+        };
+
+        match template.render(fs, &entry.path, index + 1, parent) {
+            Ok(new_name) => {
+                entry.new_name = match parent_path {
+                    Some(parent_path) => format!("{parent_path}/{new_name}"),
+                    None => new_name,
+                };
+            }
+            Err(error) => failures.push((entry.name.clone(), error)),
+        }
+    }
+
+    failures
+}
+
+/// Writes entries as an `old_name,new_name,type,fingerprint` CSV, with the
+/// listing's stamp in the fingerprint header so import can tell whether the
+/// directory changed since. The metadata `columns` follow `type`. If any
+/// name is not valid UTF-8, an `old_name_raw` column keeps the exact names.
+/// The file is written in `dialect`, or as a spreadsheet with the same
+/// columns if the path ends in `.xlsx` or `.ods`.
+pub fn write_csv(
+    fs: &impl FileSystem,
+    output_csv_path: &Path,
+    listed: &[ListedEntry],
+    stamp: &Stamp,
+    columns: &Columns,
+    dialect: &Dialect,
+) -> Result<(), Error> {
+    let rows = rows(fs, listed, stamp, columns);
+
+    if let Some(format) = spreadsheet::Format::of(output_csv_path) {
+        let write_error = |message| Error::WriteSpreadsheet {
+            path: output_csv_path.to_path_buf(),
+            message,
+        };
+        let contents = spreadsheet::write(format, &rows).map_err(write_error)?;
+        return std::fs::write(output_csv_path, contents)
+            .map_err(|error| write_error(error.to_string()));
+    }
+
+    let mut writer = dialect
+        .create(output_csv_path)
+        .map_err(|error| Error::CreateCsv {
+            path: output_csv_path.to_path_buf(),
+            source: csv::Error::from(error),
+        })?;
+
+    let write_error = |source| Error::WriteCsv {
+        path: output_csv_path.to_path_buf(),
+        source,
+    };
+
+    for row in &rows {
+        writer
+            .write_record(row.iter().map(ToString::to_string))
+            .map_err(write_error)?;
+    }
+
+    writer
+        .flush()
+        .map_err(|error| write_error(csv::Error::from(error)))
+}
+
+/// The rows `write_csv` writes, headers first. Names are text and the
+/// metadata columns keep their values' types.
+pub fn rows(
+    fs: &impl FileSystem,
+    listed: &[ListedEntry],
+    stamp: &Stamp,
+    columns: &Columns,
+) -> Vec<Vec<Value>> {
+    let text = |text: &str| Value::Text(text.to_string());
+
+    // The raw column is only written when a name needs it.
+    let raw_names = listed.iter().any(|entry| entry.raw_name.is_some());
+
+    let mut headers: Vec<Value> = ["old_name", "new_name", "type"]
+        .into_iter()
+        .chain(columns.headers())
+        .map(text)
+        .collect();
+    if raw_names {
+        headers.push(text(rawname::COLUMN));
+    }
+    headers.push(Value::Text(stamp.to_string()));
+
+    let mut rows = vec![headers];
+    for entry in listed {
+        let mut row = vec![
+            text(&entry.name),
+            text(&entry.new_name),
+            text(entry.kind.as_str()),
+        ];
+        row.extend(columns.values(fs, &entry.path));
+        if raw_names {
+            row.push(text(entry.raw_name.as_deref().unwrap_or("")));
+        }
+        row.push(Value::Text(
+            entry
+                .fingerprint
+                .map(|fingerprint| fingerprint.to_string())
+                .unwrap_or_default(),
+        ));
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Collects the names `type_filter` accepts in sorted order, each folder
+/// followed by its own contents. Nested names are `/`-separated paths relative
+/// to the exported directory. Symlinked folders are listed but not descended
+/// into.
+fn collect_entries(
+    fs: &impl FileSystem,
+    entries: Vec<DirEntry>,
+    prefix: &Path,
+    depth: usize,
+    max_depth: usize,
+    type_filter: TypeFilter,
+    listing: &mut Listing,
+) {
+    let mut children: Vec<(String, PathBuf, PathBuf, EntryKind, bool)> = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let relative = prefix.join(&entry.name);
+            Some((
+                rawname::lossy_name(&relative),
+                relative,
+                entry.path,
+                entry.kind?,
+                entry.is_symlink,
+            ))
+        })
+        .collect();
+    // Names that only differ in bytes that are not valid UTF-8 look the same,
+    // so the raw name breaks the tie.
+    children.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+
+    for (relative_name, relative, path, kind, is_symlink) in children {
+        if type_filter.accepts(kind) {
+            listing.entries.push(ListedEntry {
+                name: relative_name,
+                raw_name: rawname::encode(&relative),
+                new_name: String::new(),
+                kind,
+                fingerprint: fs.metadata(&path).ok().map(Fingerprint::from),
+                path: path.clone(),
+            });
+        }
+
+        if kind != EntryKind::Dir || depth >= max_depth || is_symlink {
+            continue;
+        }
+
+        match fs.read_dir(&path) {
+            Ok(entries) => collect_entries(
+                fs,
+                entries,
+                &relative,
+                depth + 1,
+                max_depth,
+                type_filter,
+                listing,
+            ),
+            Err(error) => listing.unreadable.push((path, error)),
+        }
+    }
+}
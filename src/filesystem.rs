@@ -0,0 +1,473 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::entry::EntryKind;
+
+/// The filesystem operations scanning, checking and renaming need. `DiskFs`
+/// is the real thing; `MemoryFs` lets the same code run against a tree that
+/// only exists in memory.
+///
+/// Journals and reports are the tool's own output and are always written to
+/// disk, as are the dates a template reads.
+pub trait FileSystem {
+    /// The kind of whatever is at `path`, following symlinks, or `None` if
+    /// nothing is there.
+    fn kind(&self, path: &Path) -> Option<EntryKind>;
+
+    /// The entries of a directory, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    /// Metadata of the entry itself; symlinks are not followed.
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// Creates a folder whose parent already exists.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes an empty folder.
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// The absolute path of an existing entry with `.`, `..` and symlinks
+    /// resolved.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.kind(path).is_some()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.kind(path) == Some(EntryKind::Dir)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.kind(path) == Some(EntryKind::File)
+    }
+
+    /// Whether both paths lead to the same entry, as two spellings of one
+    /// name do on a case-insensitive filesystem.
+    fn same_entry(&self, a: &Path, b: &Path) -> bool {
+        match (self.metadata(a), self.metadata(b)) {
+            (Ok(a), Ok(b)) => a.inode != 0 && (a.device, a.inode) == (b.device, b.inode),
+            _ => false,
+        }
+    }
+}
+
+pub struct DirEntry {
+    pub name: OsString,
+    pub path: PathBuf,
+    /// `None` for entries that vanished while being listed or are dangling
+    /// symlinks.
+    pub kind: Option<EntryKind>,
+    pub is_symlink: bool,
+}
+
+/// What the tool needs to know to tell whether an entry changed, and what
+/// export can show about it. `device` and `inode` are 0 on platforms that do
+/// not expose them; the owner, group and permission bits are only known on
+/// Unix.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub device: u64,
+    pub inode: u64,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+    pub mode: Option<u32>,
+    pub is_symlink: bool,
+}
+
+/// The real filesystem.
+pub struct DiskFs;
+
+impl FileSystem for DiskFs {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        EntryKind::of(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(path)?.flatten() {
+            let path = entry.path();
+            entries.push(DirEntry {
+                name: entry.file_name(),
+                kind: EntryKind::of(&path),
+                is_symlink: entry
+                    .file_type()
+                    .map(|file_type| file_type.is_symlink())
+                    .unwrap_or(false),
+                path,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let metadata = fs::symlink_metadata(path)?;
+
+        #[cfg(unix)]
+        let (device, inode, owner, group, mode) = {
+            use std::os::unix::fs::MetadataExt;
+            (
+                metadata.dev(),
+                metadata.ino(),
+                Some(metadata.uid()),
+                Some(metadata.gid()),
+                Some(metadata.mode()),
+            )
+        };
+        #[cfg(not(unix))]
+        let (device, inode, owner, group, mode) = (0, 0, None, None, None);
+
+        Ok(Metadata {
+            device,
+            inode,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            created: metadata.created().ok(),
+            owner,
+            group,
+            mode,
+            is_symlink: metadata.file_type().is_symlink(),
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+}
+
+/// A tree of folders and files held in memory, rooted at `/`. It has no
+/// symlinks.
+///
+/// Every change advances a clock by one second, which stands in for the
+/// modification time of the changed entry and its parent folder. Entries
+/// have no owner or group, and the permissions `rwxr-xr-x` for folders and
+/// `rw-r--r--` for files.
+///
+/// Unlike most real filesystems, `rename` never replaces an existing target;
+/// the tool checks for that itself before renaming anything.
+pub struct MemoryFs {
+    nodes: RefCell<BTreeMap<PathBuf, Node>>,
+    clock: Cell<u64>,
+    case_insensitive: bool,
+}
+
+struct Node {
+    contents: Option<Vec<u8>>,
+    inode: u64,
+    modified: u64,
+}
+
+impl Node {
+    fn kind(&self) -> EntryKind {
+        match self.contents {
+            None => EntryKind::Dir,
+            Some(_) => EntryKind::File,
+        }
+    }
+}
+
+impl MemoryFs {
+    pub fn new() -> MemoryFs {
+        let root = Node {
+            contents: None,
+            inode: 1,
+            modified: 0,
+        };
+
+        MemoryFs {
+            nodes: RefCell::new(BTreeMap::from([(PathBuf::from("/"), root)])),
+            clock: Cell::new(1),
+            case_insensitive: false,
+        }
+    }
+
+    /// A tree that, like FAT or a casefolded ext4 folder, finds names
+    /// regardless of case and Unicode normalization but keeps the spelling
+    /// they were created with.
+    pub fn case_insensitive() -> MemoryFs {
+        MemoryFs {
+            case_insensitive: true,
+            ..MemoryFs::new()
+        }
+    }
+
+    /// Creates a folder and any missing parents.
+    pub fn create_dir_all(&self, path: impl AsRef<Path>) {
+        let mut ancestors: Vec<&Path> = path.as_ref().ancestors().collect();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            if !self.exists(ancestor) {
+                self.insert(ancestor, None);
+            }
+        }
+    }
+
+    /// Creates or replaces a file, creating any missing parent folders.
+    pub fn write(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent);
+        }
+
+        let path = self.resolve(path).unwrap_or_else(|| path.to_path_buf());
+        self.insert(&path, Some(contents.into()));
+    }
+
+    /// The path an entry is stored under, which on a case-insensitive tree
+    /// may be spelled differently from `path`.
+    fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let path = &normalize(path);
+        let nodes = self.nodes.borrow();
+        if nodes.contains_key(path) {
+            return Some(path.to_path_buf());
+        }
+        if !self.case_insensitive {
+            return None;
+        }
+
+        let folded = fold(path);
+        nodes.keys().find(|key| fold(key) == folded).cloned()
+    }
+
+    fn insert(&self, path: &Path, contents: Option<Vec<u8>>) {
+        let now = self.tick();
+        let mut nodes = self.nodes.borrow_mut();
+
+        // The clock never repeats, so it doubles as the inode number.
+        nodes.insert(
+            path.to_path_buf(),
+            Node {
+                contents,
+                inode: now,
+                modified: now,
+            },
+        );
+        touch(&mut nodes, path.parent(), now);
+    }
+
+    fn tick(&self) -> u64 {
+        let now = self.clock.get();
+        self.clock.set(now + 1);
+        now
+    }
+
+    /// Every path in the tree below `/`, sorted.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.nodes
+            .borrow()
+            .keys()
+            .filter(|path| path.parent().is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+impl Default for MemoryFs {
+    fn default() -> MemoryFs {
+        MemoryFs::new()
+    }
+}
+
+/// `path` from the root with `.` and `..` resolved by name, which is where
+/// they lead without symlinks.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+    normalized
+}
+
+fn fold(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase().nfc().collect()
+}
+
+fn touch(nodes: &mut BTreeMap<PathBuf, Node>, path: Option<&Path>, now: u64) {
+    if let Some(node) = path.and_then(|path| nodes.get_mut(path)) {
+        node.modified = now;
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        let path = self.resolve(path)?;
+        self.nodes.borrow().get(&path).map(Node::kind)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        match self.kind(path) {
+            Some(EntryKind::Dir) => {}
+            Some(EntryKind::File) => return Err(io::ErrorKind::NotADirectory.into()),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        }
+        let path = self.resolve(path).ok_or(io::ErrorKind::NotFound)?;
+
+        Ok(self
+            .nodes
+            .borrow()
+            .iter()
+            .filter(|(child, _)| child.parent() == Some(path.as_path()))
+            .map(|(child, node)| DirEntry {
+                name: child.file_name().unwrap_or_default().to_os_string(),
+                path: child.clone(),
+                kind: Some(node.kind()),
+                is_symlink: false,
+            })
+            .collect())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let from = self.resolve(from).ok_or(io::ErrorKind::NotFound)?;
+        let to = match (
+            to.parent().and_then(|parent| self.resolve(parent)),
+            to.file_name(),
+        ) {
+            (Some(parent), Some(name)) if self.is_dir(&parent) => parent.join(name),
+            _ => return Err(io::ErrorKind::NotFound.into()),
+        };
+        // A case-insensitive tree finds the source under its new spelling.
+        if self.resolve(&to).is_some_and(|existing| existing != from) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        if to != from && to.starts_with(&from) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        let now = self.tick();
+        let mut nodes = self.nodes.borrow_mut();
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|path| path.starts_with(&from))
+            .cloned()
+            .collect();
+
+        for path in moved {
+            if let Some(node) = nodes.remove(&path) {
+                let moved_to = match path.strip_prefix(&from) {
+                    Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                    _ => to.clone(),
+                };
+                nodes.insert(moved_to, node);
+            }
+        }
+        touch(&mut nodes, from.parent(), now);
+        touch(&mut nodes, to.parent(), now);
+
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        if self.exists(path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        let path = match (
+            path.parent().and_then(|parent| self.resolve(parent)),
+            path.file_name(),
+        ) {
+            (Some(parent), Some(name)) if self.is_dir(&parent) => parent.join(name),
+            _ => return Err(io::ErrorKind::NotFound.into()),
+        };
+
+        self.insert(&path, None);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        match self.kind(path) {
+            Some(EntryKind::Dir) => {}
+            Some(EntryKind::File) => return Err(io::ErrorKind::NotADirectory.into()),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        }
+        if !self.read_dir(path)?.is_empty() {
+            return Err(io::ErrorKind::DirectoryNotEmpty.into());
+        }
+        let path = self.resolve(path).ok_or(io::ErrorKind::NotFound)?;
+
+        let now = self.tick();
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.remove(&path);
+        touch(&mut nodes, path.parent(), now);
+
+        Ok(())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        let path = self.resolve(path).ok_or(io::ErrorKind::NotFound)?;
+        let nodes = self.nodes.borrow();
+        let node = nodes.get(&path).ok_or(io::ErrorKind::NotFound)?;
+
+        Ok(Metadata {
+            device: 1,
+            inode: node.inode,
+            size: node
+                .contents
+                .as_ref()
+                .map_or(0, |contents| contents.len() as u64),
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(node.modified)),
+            // The inode is the time the entry was created.
+            created: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(node.inode)),
+            owner: None,
+            group: None,
+            mode: Some(match node.kind() {
+                EntryKind::Dir => 0o040755,
+                EntryKind::File => 0o100644,
+            }),
+            is_symlink: false,
+        })
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let path = self.resolve(path).ok_or(io::ErrorKind::NotFound)?;
+        match self.nodes.borrow().get(&path) {
+            Some(Node {
+                contents: Some(contents),
+                ..
+            }) => Ok(contents.clone()),
+            Some(_) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve(path).ok_or(io::ErrorKind::NotFound.into())
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::Error;
+use crate::entry::TypeFilter;
+use crate::export::{self, ExportOptions, ListedEntry};
+use crate::filesystem::{FileSystem, Metadata};
+use crate::plan::PlannedRename;
+
+/// Header of the CSV column that holds each entry's fingerprint. The header
+/// cell itself carries the `Stamp` of the whole listing.
+pub const COLUMN: &str = "fingerprint";
+
+/// The identity and state of one entry when it was exported, written as
+/// `device:inode:size:seconds.nanoseconds` (the time is `-` if unknown).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    device: u64,
+    inode: u64,
+    size: u64,
+    modified: Option<(u64, u32)>,
+}
+
+impl From<Metadata> for Fingerprint {
+    fn from(metadata: Metadata) -> Fingerprint {
+        let modified = metadata
+            .modified
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|since_epoch| (since_epoch.as_secs(), since_epoch.subsec_nanos()));
+
+        Fingerprint {
+            device: metadata.device,
+            inode: metadata.inode,
+            size: metadata.size,
+            modified,
+        }
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}:", self.device, self.inode, self.size)?;
+        match self.modified {
+            Some((seconds, nanos)) => write!(f, "{seconds}.{nanos:09}"),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+impl FromStr for Fingerprint {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Fingerprint, ()> {
+        let mut parts = value.trim().split(':');
+        let mut number = || parts.next().ok_or(())?.parse::<u64>().map_err(|_| ());
+        let (device, inode, size) = (number()?, number()?, number()?);
+
+        let modified = match parts.next().ok_or(())? {
+            "-" => None,
+            modified => {
+                let (seconds, nanos) = modified.split_once('.').ok_or(())?;
+                let seconds = seconds.parse().map_err(|_| ())?;
+                let nanos = nanos.parse::<u32>().map_err(|_| ())?;
+                if nanos >= 1_000_000_000 {
+                    return Err(());
+                }
+                Some((seconds, nanos))
+            }
+        };
+
+        if parts.next().is_some() {
+            return Err(());
+        }
+
+        Ok(Fingerprint {
+            device,
+            inode,
+            size,
+            modified,
+        })
+    }
+}
+
+/// How a listing was made and a hash of every entry in it, so a later scan
+/// can tell whether anything changed. Written into the header of the
+/// fingerprint column as `fingerprint depth=<n|all> type=<filter> hash=<hex>`.
+pub struct Stamp {
+    pub max_depth: Option<usize>,
+    pub type_filter: TypeFilter,
+    pub hash: u64,
+}
+
+impl Stamp {
+    /// Stamps a listing made with `options`.
+    pub fn new(options: &ExportOptions, entries: &[ListedEntry]) -> Stamp {
+        let max_depth = match options.max_depth {
+            Some(max_depth) => Some(max_depth),
+            None if options.recursive => None,
+            None => Some(1),
+        };
+
+        Stamp {
+            max_depth,
+            type_filter: options.type_filter,
+            hash: listing_hash(entries),
+        }
+    }
+
+    pub(crate) fn rehash(&mut self, entries: &[ListedEntry]) {
+        self.hash = listing_hash(entries);
+    }
+
+    /// The options that repeat the stamped listing.
+    pub fn options(&self) -> ExportOptions {
+        ExportOptions {
+            recursive: self.max_depth.is_none(),
+            max_depth: self.max_depth,
+            type_filter: self.type_filter,
+        }
+    }
+}
+
+impl fmt::Display for Stamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{COLUMN} depth=")?;
+        match self.max_depth {
+            Some(max_depth) => write!(f, "{max_depth}")?,
+            None => f.write_str("all")?,
+        }
+        write!(
+            f,
+            " type={} hash={:016x}",
+            self.type_filter.as_str(),
+            self.hash
+        )
+    }
+}
+
+impl FromStr for Stamp {
+    type Err = String;
+
+    fn from_str(header: &str) -> Result<Stamp, String> {
+        let mut words = header.split_whitespace();
+        if words.next() != Some(COLUMN) {
+            return Err(format!("expected a {COLUMN} column"));
+        }
+
+        let (mut max_depth, mut type_filter, mut hash) = (None, None, None);
+
+        for word in words {
+            let invalid = || format!("invalid setting {word:?}");
+            let (key, value) = word.split_once('=').ok_or_else(invalid)?;
+
+            match key {
+                "depth" if value == "all" => max_depth = Some(None),
+                "depth" => max_depth = Some(Some(value.parse().map_err(|_| invalid())?)),
+                "type" => type_filter = Some(value.parse().map_err(|()| invalid())?),
+                "hash" => hash = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?),
+                _ => return Err(invalid()),
+            }
+        }
+
+        match (max_depth, type_filter, hash) {
+            (Some(max_depth), Some(type_filter), Some(hash)) => Ok(Stamp {
+                max_depth,
+                type_filter,
+                hash,
+            }),
+            _ => Err("depth, type and hash are required".to_string()),
+        }
+    }
+}
+
+/// What changed in a directory since its CSV was exported.
+#[derive(Default)]
+pub struct Staleness {
+    /// Whether the listing as a whole differs from the exported one.
+    pub stale: bool,
+    /// Rows whose entry is still there but was modified or replaced.
+    pub changed: Vec<String>,
+    /// Rows whose entry is gone.
+    pub vanished: Vec<String>,
+    /// Entries the CSV has no row for, either added since export or left out
+    /// of the CSV by whoever edited it.
+    pub unlisted: Vec<String>,
+}
+
+/// Scans `directory` again the way the stamped export did and compares it
+/// with the fingerprints recorded in the plan read from `csv_path`. The CSV
+/// itself is left out, as export leaves it out.
+pub fn check(
+    fs: &impl FileSystem,
+    directory: &Path,
+    csv_path: &Path,
+    stamp: &Stamp,
+    plan: &[PlannedRename],
+) -> Result<Staleness, Error> {
+    let options = stamp.options();
+    let mut listing = export::list_entries(fs, directory, &options)?;
+    listing.exclude(csv_path);
+
+    if listing.stamp.hash == stamp.hash {
+        return Ok(Staleness::default());
+    }
+
+    let current: HashMap<&str, Option<Fingerprint>> = listing
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), entry.fingerprint))
+        .collect();
+
+    let mut staleness = Staleness {
+        stale: true,
+        ..Staleness::default()
+    };
+
+    for rename in plan.iter().filter(|rename| !rename.old_name.is_empty()) {
+        match current.get(rename.old_name.as_str()) {
+            None => staleness.vanished.push(rename.old_name.clone()),
+            Some(fingerprint) if *fingerprint != rename.fingerprint => {
+                staleness.changed.push(rename.old_name.clone())
+            }
+            Some(_) => {}
+        }
+    }
+
+    let listed: HashSet<&str> = plan.iter().map(|rename| rename.old_name.as_str()).collect();
+    staleness.unlisted = listing
+        .entries
+        .iter()
+        .filter(|entry| !listed.contains(entry.name.as_str()))
+        .map(|entry| entry.name.clone())
+        .collect();
+
+    Ok(staleness)
+}
+
+/// 64-bit FNV-1a over every name and fingerprint, which unlike the standard
+/// library's hasher is stable across Rust releases.
+fn listing_hash(entries: &[ListedEntry]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for entry in entries {
+        let fingerprint = entry
+            .fingerprint
+            .map(|fingerprint| fingerprint.to_string())
+            .unwrap_or_default();
+
+        for byte in entry
+            .name
+            .bytes()
+            .chain([0])
+            .chain(fingerprint.bytes())
+            .chain([b'\n'])
+        {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    hash
+}
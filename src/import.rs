@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::dialect;
+use crate::entry::EntryKind;
+use crate::filesystem::FileSystem;
+use crate::fingerprint::{self, Stamp};
+use crate::plan::{self, PlannedRename, SkipReason};
+use crate::rawname;
+use crate::spreadsheet;
+
+/// A plan read from a CSV, with the stamp of the export it came from if the
+/// CSV has a fingerprint column.
+pub struct CsvPlan {
+    pub plan: Vec<PlannedRename>,
+    pub stamp: Option<Stamp>,
+}
+
+/// How `read_plan` reads a CSV and where it finds the names. A column is
+/// given by its header or by its number, counted from 1; without a header
+/// row only numbers work, and the first two columns are the default.
+#[derive(Default)]
+pub struct CsvOptions {
+    /// The column of old names, `old_name` by default.
+    pub old_column: Option<String>,
+    /// The column of new names, `new_name` by default.
+    pub new_column: Option<String>,
+    /// The first row is a rename like any other.
+    pub no_header: bool,
+    /// The delimiter, guessed from the first line if not given.
+    pub delimiter: Option<u8>,
+    /// The quote character, `"` if not given.
+    pub quote: Option<u8>,
+}
+
+/// Reads a CSV with `old_name` and `new_name` columns, and optionally
+/// `type`, `old_name_raw` and `fingerprint` ones, into a plan for
+/// `directory`. Columns may be in any order and other columns are ignored.
+/// The encoding is detected as `dialect::decode` describes. An `.xlsx` or
+/// `.ods` file is read from its first sheet instead, where the delimiter
+/// and quote options do not apply.
+/// Headers are matched regardless of case, a leading byte order mark and
+/// surrounding whitespace, and spaces or dashes stand for underscores, so
+/// `Old Name` finds `old_name`.
+///
+/// Rows that cannot be used at all are marked as skipped; everything else
+/// is left for `plan::check_simultaneous`.
+pub fn read_plan(
+    fs: &impl FileSystem,
+    directory: &Path,
+    csv_path: &Path,
+    options: &CsvOptions,
+) -> Result<CsvPlan, Error> {
+    if !fs.is_dir(directory) {
+        return Err(Error::NotADirectory(directory.to_path_buf()));
+    }
+
+    if !fs.is_file(csv_path) {
+        return Err(Error::NotACsvFile(csv_path.to_path_buf()));
+    }
+
+    let read_error = |source| Error::ReadCsv {
+        path: csv_path.to_path_buf(),
+        source,
+    };
+
+    let contents = fs
+        .read(csv_path)
+        .map_err(|error| read_error(csv::Error::from(error)))?;
+    let (raw_headers, records) = match spreadsheet::Format::of(csv_path) {
+        Some(_) => {
+            read_sheet_records(contents, options).map_err(|message| Error::ReadSpreadsheet {
+                path: csv_path.to_path_buf(),
+                message,
+            })?
+        }
+        None => read_records(&contents, options).map_err(read_error)?,
+    };
+
+    let headers: Vec<String> = raw_headers.iter().map(header_key).collect();
+    let find = |name: &str| headers.iter().position(|header| header == name);
+
+    let old_name_header = options.old_column.as_deref().unwrap_or("old_name");
+    let new_name_header = options.new_column.as_deref().unwrap_or("new_name");
+    let (old_column, new_column) = if options.no_header {
+        (
+            options.old_column.as_deref().map_or(Some(0), column_number),
+            options.new_column.as_deref().map_or(Some(1), column_number),
+        )
+    } else {
+        let locate = |column: &str| column_number(column).or_else(|| find(&header_key(column)));
+        (locate(old_name_header), locate(new_name_header))
+    };
+
+    let (Some(old_column), Some(new_column)) = (old_column, new_column) else {
+        return Err(Error::InvalidHeaders {
+            path: csv_path.to_path_buf(),
+            expected: if options.no_header {
+                "column numbers, as there is no header row".to_string()
+            } else {
+                format!("{old_name_header},{new_name_header}")
+            },
+        });
+    };
+
+    let type_column = find("type");
+    let fingerprint_column = headers
+        .iter()
+        .position(|header| header.split('_').next() == Some(fingerprint::COLUMN));
+
+    let stamp = fingerprint_column
+        .and_then(|column| raw_headers.get(column))
+        .map(|header| {
+            header
+                .parse::<Stamp>()
+                .map_err(|message| Error::InvalidFingerprint {
+                    path: csv_path.to_path_buf(),
+                    message,
+                })
+        })
+        .transpose()?;
+
+    let raw_column = find(rawname::COLUMN);
+    let mut plan = Vec::new();
+
+    for (row, result) in records {
+        let record = match result {
+            Ok(record) => record,
+            Err(error) => {
+                let mut rename = PlannedRename::new(
+                    row,
+                    String::new(),
+                    String::new(),
+                    PathBuf::new(),
+                    PathBuf::new(),
+                );
+                rename.skip = Some(SkipReason::UnreadableRow(error));
+                plan.push(rename);
+                continue;
+            }
+        };
+
+        let old_name = record.get(old_column).unwrap_or("").trim().to_string();
+        let new_name = record.get(new_column).unwrap_or("").trim().to_string();
+        let kind = type_column
+            .and_then(|column| record.get(column))
+            .map(str::trim)
+            .unwrap_or("");
+
+        // The raw name, if there is one, is the exact name on disk.
+        let raw_name = raw_column
+            .and_then(|column| record.get(column))
+            .map(str::trim)
+            .filter(|raw_name| !raw_name.is_empty());
+        let old_path = match raw_name.map(rawname::decode) {
+            Some(Some(relative)) => directory.join(relative),
+            _ => directory.join(&old_name),
+        };
+        let new_path = plan::target_path(directory, &old_path, &old_name, &new_name);
+
+        let mut rename =
+            PlannedRename::new(row, old_name.clone(), new_name.clone(), old_path, new_path);
+        rename.fingerprint = fingerprint_column
+            .and_then(|column| record.get(column))
+            .and_then(|fingerprint| fingerprint.parse().ok());
+
+        if old_name.is_empty() || new_name.is_empty() {
+            rename.skip = Some(SkipReason::EmptyName);
+        } else if let Some(raw_name) = raw_name.filter(|raw| rawname::decode(raw).is_none()) {
+            rename.skip = Some(SkipReason::InvalidRawName(raw_name.to_string()));
+        } else if !kind.is_empty() {
+            match kind.parse::<EntryKind>() {
+                Ok(kind) => rename.expected_kind = Some(kind),
+                Err(()) => rename.skip = Some(SkipReason::InvalidType(kind.to_string())),
+            }
+        }
+
+        plan.push(rename);
+    }
+
+    Ok(CsvPlan { plan, stamp })
+}
+
+/// A record with its row number, or why that row could not be read.
+type Record = (usize, csv::Result<csv::StringRecord>);
+
+/// The header and other records of a CSV.
+fn read_records(
+    contents: &[u8],
+    options: &CsvOptions,
+) -> Result<(csv::StringRecord, Vec<Record>), csv::Error> {
+    let (text, _) = dialect::decode(contents);
+    let quote = options.quote.unwrap_or(b'"');
+    let delimiter = options
+        .delimiter
+        .unwrap_or_else(|| dialect::detect_delimiter(&text, quote));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(!options.no_header)
+        .delimiter(delimiter)
+        .quote(quote)
+        .from_reader(text.as_bytes());
+    let raw_headers = if options.no_header {
+        csv::StringRecord::new()
+    } else {
+        reader.headers()?.clone()
+    };
+
+    let first_row = if options.no_header { 1 } else { 2 };
+    let records = reader
+        .into_records()
+        .enumerate()
+        .map(|(index, record)| (index + first_row, record))
+        .collect();
+
+    Ok((raw_headers, records))
+}
+
+/// The header and other records of a spreadsheet's first sheet.
+fn read_sheet_records(
+    contents: Vec<u8>,
+    options: &CsvOptions,
+) -> Result<(csv::StringRecord, Vec<Record>), String> {
+    let mut rows = spreadsheet::read(contents)?.into_iter();
+    let raw_headers = if options.no_header {
+        csv::StringRecord::new()
+    } else {
+        rows.next()
+            .map(|(_, headers)| csv::StringRecord::from(headers))
+            .unwrap_or_default()
+    };
+    let records = rows
+        .map(|(row, cells)| (row, Ok(csv::StringRecord::from(cells))))
+        .collect();
+
+    Ok((raw_headers, records))
+}
+
+/// A header as it is matched: without a byte order mark or surrounding
+/// whitespace, in lowercase, with spaces and dashes as underscores.
+fn header_key(header: &str) -> String {
+    header
+        .trim_start_matches('\u{feff}')
+        .trim()
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The index of a column given by its number, counted from 1.
+fn column_number(column: &str) -> Option<usize> {
+    column.trim().parse::<usize>().ok()?.checked_sub(1)
+}
@@ -0,0 +1,293 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::Error;
+use crate::apply::Outcome;
+use crate::datetime::format_timestamp;
+use crate::filesystem::FileSystem;
+use crate::plan::PlannedRename;
+use crate::rawname;
+
+const HEADERS: [&str; 8] = [
+    "timestamp",
+    "row",
+    "outcome",
+    "old_path",
+    "new_path",
+    "reason",
+    "old_path_raw",
+    "new_path_raw",
+];
+
+/// A CSV record of every row an import or undo run processed, written as the
+/// run progresses so it survives an interrupted run.
+pub struct Journal {
+    writer: csv::Writer<File>,
+    path: PathBuf,
+}
+
+/// A folder a run created before moving something into it, or removed once
+/// it was left empty.
+#[derive(Clone, Copy)]
+pub enum FolderAction {
+    Created,
+    Removed,
+}
+
+impl FolderAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FolderAction::Created => "created",
+            FolderAction::Removed => "removed",
+        }
+    }
+}
+
+/// What a journal says a run did.
+pub struct Journaled {
+    /// The renames that were performed, in the order they ran.
+    pub renames: Vec<JournalEntry>,
+    /// The folders that were created, in the order they were created.
+    pub created: Vec<PathBuf>,
+}
+
+/// A rename the journal says was actually performed.
+pub struct JournalEntry {
+    pub line: usize,
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    /// The rename reversed an earlier one when its run was rolled back.
+    pub rollback: bool,
+}
+
+impl Journal {
+    pub fn create(path: &Path) -> Result<Journal, Error> {
+        // Never overwrite an earlier journal; it may be the only record of a run.
+        let file = File::create_new(path).map_err(|source| Error::CreateJournal {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let mut journal = Journal {
+            writer: csv::Writer::from_writer(file),
+            path: path.to_path_buf(),
+        };
+        journal.write(HEADERS)?;
+
+        Ok(journal)
+    }
+
+    pub fn record(&mut self, rename: &PlannedRename, outcome: &Outcome) -> Result<(), Error> {
+        let (outcome, reason) = match outcome {
+            Outcome::Renamed => ("renamed", String::new()),
+            Outcome::Skipped { message, .. } => ("skipped", message.clone()),
+            Outcome::Failed(reason) => ("failed", reason.clone()),
+            Outcome::RolledBack => ("rolled_back", String::new()),
+            Outcome::RollbackFailed(reason) => ("failed", format!("rollback failed: {reason}")),
+        };
+
+        let old_path_raw = rawname::encode(&rename.old_path).unwrap_or_default();
+        let new_path_raw = rawname::encode(&rename.new_path).unwrap_or_default();
+
+        self.write([
+            format_timestamp(SystemTime::now()).as_str(),
+            rename.row.to_string().as_str(),
+            outcome,
+            rename.old_path.to_string_lossy().as_ref(),
+            rename.new_path.to_string_lossy().as_ref(),
+            reason.as_str(),
+            old_path_raw.as_str(),
+            new_path_raw.as_str(),
+        ])
+    }
+
+    /// Records a folder the run created or removed, with the row that caused
+    /// it if there is one.
+    pub fn record_folder(
+        &mut self,
+        row: Option<usize>,
+        action: FolderAction,
+        path: &Path,
+    ) -> Result<(), Error> {
+        let row = row.map(|row| row.to_string()).unwrap_or_default();
+        let (old_path, new_path) = match action {
+            FolderAction::Created => (Path::new(""), path),
+            FolderAction::Removed => (path, Path::new("")),
+        };
+        let old_path_raw = rawname::encode(old_path).unwrap_or_default();
+        let new_path_raw = rawname::encode(new_path).unwrap_or_default();
+
+        self.write([
+            format_timestamp(SystemTime::now()).as_str(),
+            row.as_str(),
+            action.as_str(),
+            old_path.to_string_lossy().as_ref(),
+            new_path.to_string_lossy().as_ref(),
+            "",
+            old_path_raw.as_str(),
+            new_path_raw.as_str(),
+        ])
+    }
+
+    fn write<'a>(&mut self, record: impl IntoIterator<Item = &'a str>) -> Result<(), Error> {
+        self.writer
+            .write_record(record)
+            .and_then(|()| self.writer.flush().map_err(csv::Error::from))
+            .map_err(|source| Error::WriteJournal {
+                path: self.path.clone(),
+                source,
+            })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Default journal location: a timestamped file in the current directory.
+pub fn default_journal_path() -> PathBuf {
+    timestamped_path("rename_journal")
+}
+
+/// An unused `<prefix>_<timestamp>.csv` in the current directory.
+pub(crate) fn timestamped_path(prefix: &str) -> PathBuf {
+    let timestamp = format_timestamp(SystemTime::now()).replace(['-', ':'], "");
+    let mut path = PathBuf::from(format!("{prefix}_{timestamp}.csv"));
+
+    let mut attempt = 1;
+    while path.exists() {
+        path = PathBuf::from(format!("{prefix}_{timestamp}_{attempt}.csv"));
+        attempt += 1;
+    }
+
+    path
+}
+
+/// Reads the renames a journal recorded as performed and the folders it
+/// recorded as created.
+pub fn read_journal(fs: &impl FileSystem, journal_path: &Path) -> Result<Journaled, Error> {
+    if !fs.is_file(journal_path) {
+        return Err(Error::NotAJournalFile(journal_path.to_path_buf()));
+    }
+
+    let read_error = |source| Error::ReadJournal {
+        path: journal_path.to_path_buf(),
+        source,
+    };
+    let invalid = |message| Error::InvalidJournal {
+        path: journal_path.to_path_buf(),
+        message,
+    };
+
+    let contents = fs
+        .read(journal_path)
+        .map_err(|error| read_error(csv::Error::from(error)))?;
+    let mut reader = csv::Reader::from_reader(contents.as_slice());
+
+    let headers = reader.headers().map_err(read_error)?;
+    if !headers.iter().eq(HEADERS) {
+        return Err(invalid(format!(
+            "unexpected headers, expected {}",
+            HEADERS.join(",")
+        )));
+    }
+
+    let mut journaled = Journaled {
+        renames: Vec::new(),
+        created: Vec::new(),
+    };
+
+    for (index, result) in reader.records().enumerate() {
+        let line = index + 2;
+        let record = result.map_err(read_error)?;
+
+        let outcome = record.get(2);
+        let rollback = outcome == Some("rolled_back");
+        if outcome != Some("renamed")
+            && outcome != Some(FolderAction::Created.as_str())
+            && !rollback
+        {
+            continue;
+        }
+
+        let path = |column: usize, raw_column: usize| -> Result<PathBuf, Error> {
+            match record.get(raw_column).filter(|raw| !raw.is_empty()) {
+                Some(raw) => rawname::decode(raw)
+                    .ok_or_else(|| invalid(format!("row {line} has an invalid raw path"))),
+                None => Ok(PathBuf::from(record.get(column).unwrap_or(""))),
+            }
+        };
+        let new_path = path(4, 7)?;
+        if !new_path.is_absolute() {
+            return Err(invalid(format!("row {line} paths must be absolute")));
+        }
+
+        if outcome == Some(FolderAction::Created.as_str()) {
+            journaled.created.push(new_path);
+            continue;
+        }
+
+        let old_path = path(3, 6)?;
+        if !old_path.is_absolute() {
+            return Err(invalid(format!("row {line} paths must be absolute")));
+        }
+
+        journaled.renames.push(JournalEntry {
+            line,
+            old_path,
+            new_path,
+            rollback,
+        });
+    }
+
+    Ok(journaled)
+}
+
+/// The plan that reverses a journal's renames, newest first. Check it with
+/// `plan::check_sequential` before executing it.
+///
+/// A rename that was rolled back is left out together with its rollback,
+/// as the run already reversed it. The folders entries are moved back into
+/// may have been removed when they were left empty, so the plan creates
+/// them again.
+pub fn undo_plan(entries: Vec<JournalEntry>) -> Vec<PlannedRename> {
+    let mut performed: Vec<Option<JournalEntry>> = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let reversed = entry
+            .rollback
+            .then(|| {
+                performed.iter().rposition(|earlier| {
+                    earlier.as_ref().is_some_and(|earlier| {
+                        !earlier.rollback
+                            && earlier.old_path == entry.new_path
+                            && earlier.new_path == entry.old_path
+                    })
+                })
+            })
+            .flatten();
+
+        match reversed {
+            Some(index) => performed[index] = None,
+            None => performed.push(Some(entry)),
+        }
+    }
+
+    performed
+        .into_iter()
+        .flatten()
+        .rev()
+        .map(|entry| {
+            let mut rename = PlannedRename::new(
+                entry.line,
+                entry.new_path.display().to_string(),
+                entry.old_path.display().to_string(),
+                entry.new_path,
+                entry.old_path,
+            );
+            rename.create_parents = true;
+            rename
+        })
+        .collect()
+}
@@ -0,0 +1,45 @@
+//! Bulk renaming of folders and files driven by `old_name,new_name` plans.
+//!
+//! A plan is read from a CSV (`import`), generated from a directory scan
+//! (`export`, `substitute`, `edit`, `tui`) or from a journal, checked without
+//! touching disk (`plan`), and then executed (`apply`). Every step that
+//! looks at the tree goes through a `filesystem::FileSystem`, so the same
+//! plans can be run against `MemoryFs` instead of the disk.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+pub mod apply;
+pub mod columns;
+pub mod datetime;
+pub mod dialect;
+pub mod edit;
+pub mod entry;
+pub mod error;
+pub mod export;
+pub mod filesystem;
+pub mod fingerprint;
+pub mod import;
+pub mod journal;
+pub mod plan;
+pub mod rawname;
+pub mod report;
+pub mod review;
+pub mod spreadsheet;
+pub mod substitute;
+pub mod template;
+pub mod tui;
+pub mod validate;
+
+pub use error::Error;
+
+/// Makes a path absolute against the current directory.
+pub fn resolve_path(path: &Path) -> Result<PathBuf, Error> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        env::current_dir()
+            .map(|current_dir| current_dir.join(path))
+            .map_err(Error::CurrentDir)
+    }
+}
@@ -1,6 +1,32 @@
+use std::collections::HashSet;
 use std::env;
-use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rename_tool::apply::{self, Outcome, StopPolicy};
+use rename_tool::columns::{Column, Columns};
+use rename_tool::dialect::Dialect;
+use rename_tool::edit;
+use rename_tool::export::{self, ExportOptions, ListedEntry, Listing};
+use rename_tool::filesystem::DiskFs;
+use rename_tool::fingerprint::{self, Staleness};
+use rename_tool::import::{self, CsvOptions, CsvPlan};
+use rename_tool::journal::{self, Journal};
+use rename_tool::plan::{self, CheckOptions, PlannedRename};
+use rename_tool::report::{Report, ReportFormat};
+use rename_tool::review;
+use rename_tool::spreadsheet;
+use rename_tool::substitute::{self, RegexOptions};
+use rename_tool::template::Template;
+use rename_tool::tui::{self, Session};
+use rename_tool::validate::{self, Profile};
+use rename_tool::{Error, resolve_path};
+
+/// Exit codes for import and the commands that share its engine. Usage and
+/// setup errors (a missing directory, an unreadable CSV) exit with 1.
+const EXIT_PARTIAL: i32 = 2;
+const EXIT_VALIDATION: i32 = 3;
+const EXIT_IO: i32 = 4;
 
 fn main() {
     let mut args = env::args().skip(1);
@@ -12,40 +38,246 @@ fn main() {
 
     match command.as_str() {
         "export" => {
-            let Some(directory_path) = args.next() else {
+            let mut options = ExportOptions::default();
+            let mut template = None;
+            let mut columns = Vec::new();
+            let mut excel = false;
+            let mut delimiter = None;
+            let mut quote = None;
+            let mut check = false;
+            let mut profile = Profile::default();
+            let mut positional = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--recursive" => options.recursive = true,
+                    "--max-depth" => options.max_depth = Some(parse_flag_value(&mut args)),
+                    "--type" => options.type_filter = parse_flag_value(&mut args),
+                    "--template" => template = Some(flag_value(&mut args)),
+                    "--columns" => columns = parse_columns(&flag_value(&mut args)),
+                    "--excel" => excel = true,
+                    "--delimiter" => delimiter = Some(parse_csv_char(&flag_value(&mut args))),
+                    "--quote" => quote = Some(parse_csv_char(&flag_value(&mut args))),
+                    "--check" => check = true,
+                    "--profile" => profile = parse_flag_value(&mut args),
+                    flag if flag.starts_with("--") => {
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                    _ => positional.push(arg),
+                }
+            }
+
+            let mut positional = positional.into_iter();
+
+            let Some(directory_path) = positional.next() else {
                 print_usage();
                 std::process::exit(1);
             };
 
-            let output_csv = args
+            if check {
+                if positional.next().is_some() {
+                    print_usage();
+                    std::process::exit(1);
+                }
+                check_names(PathBuf::from(directory_path), &options, profile);
+                return;
+            }
+
+            let output_csv = positional
                 .next()
                 .map(PathBuf::from)
                 .unwrap_or_else(|| PathBuf::from("folders.csv"));
 
-            if args.next().is_some() {
+            if positional.next().is_some() {
                 print_usage();
                 std::process::exit(1);
             }
 
-            export(PathBuf::from(directory_path), output_csv);
+            // --delimiter and --quote override the preset.
+            let mut dialect = if excel {
+                Dialect::excel()
+            } else {
+                Dialect::default()
+            };
+            dialect.delimiter = delimiter.unwrap_or(dialect.delimiter);
+            dialect.quote = quote.unwrap_or(dialect.quote);
+
+            export(
+                PathBuf::from(directory_path),
+                output_csv,
+                options,
+                template.as_deref(),
+                Columns::new(columns),
+                dialect,
+            );
         }
         "import" => {
-            let Some(directory_path) = args.next() else {
+            let mut options = ImportOptions::default();
+            let mut positional = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--force" => options.force = true,
+                    "--allow-move-outside" => options.check.allow_move_outside = true,
+                    "--interactive" => options.interactive = true,
+                    "--decisions" => options.decisions = Some(PathBuf::from(flag_value(&mut args))),
+                    "--old-col" => options.csv.old_column = Some(flag_value(&mut args)),
+                    "--new-col" => options.csv.new_column = Some(flag_value(&mut args)),
+                    "--no-header" => options.csv.no_header = true,
+                    "--delimiter" => {
+                        options.csv.delimiter = Some(parse_csv_char(&flag_value(&mut args)))
+                    }
+                    "--quote" => options.csv.quote = Some(parse_csv_char(&flag_value(&mut args))),
+                    _ if parse_import_flag(&arg, &mut args, &mut options) => {}
+                    flag if flag.starts_with("--") => {
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                    _ => positional.push(arg),
+                }
+            }
+
+            let mut positional = positional.into_iter();
+
+            let Some(directory_path) = positional.next() else {
                 print_usage();
                 std::process::exit(1);
             };
 
-            let input_csv = args
+            let input_csv = positional
                 .next()
                 .map(PathBuf::from)
                 .unwrap_or_else(|| PathBuf::from("folders.csv"));
 
-            if args.next().is_some() {
+            if positional.next().is_some() {
+                print_usage();
+                std::process::exit(1);
+            }
+
+            import(PathBuf::from(directory_path), input_csv, options);
+        }
+        "regex" => {
+            let mut options = RegexOptions::default();
+            let mut listing = ExportOptions::default();
+            let mut output = None;
+            let mut import_options = ImportOptions::default();
+            let mut positional = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "-i" | "--ignore-case" => options.ignore_case = true,
+                    "--all" => options.replace_all = true,
+                    "--filter" => options.filter = Some(flag_value(&mut args)),
+                    "--output" => output = Some(PathBuf::from(flag_value(&mut args))),
+                    _ if parse_run_flag(&arg, &mut args, &mut listing, &mut import_options) => {}
+                    flag if flag.starts_with("--") => {
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                    _ => positional.push(arg),
+                }
+            }
+
+            let [directory_path, pattern, replacement] = <[String; 3]>::try_from(positional)
+                .unwrap_or_else(|_| {
+                    print_usage();
+                    std::process::exit(1);
+                });
+
+            regex_rename(
+                PathBuf::from(directory_path),
+                &pattern,
+                &replacement,
+                RegexRun {
+                    listing,
+                    options,
+                    output,
+                },
+                import_options,
+            );
+        }
+        "edit" => {
+            let mut listing = ExportOptions::default();
+            let mut import_options = ImportOptions::default();
+            let mut positional = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    _ if parse_run_flag(&arg, &mut args, &mut listing, &mut import_options) => {}
+                    flag if flag.starts_with("--") => {
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                    _ => positional.push(arg),
+                }
+            }
+
+            let [directory_path] = <[String; 1]>::try_from(positional).unwrap_or_else(|_| {
                 print_usage();
                 std::process::exit(1);
+            });
+
+            edit(PathBuf::from(directory_path), listing, import_options);
+        }
+        "tui" => {
+            let mut listing = ExportOptions::default();
+            let mut import_options = ImportOptions::default();
+            let mut positional = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    _ if parse_run_flag(&arg, &mut args, &mut listing, &mut import_options) => {}
+                    flag if flag.starts_with("--") => {
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                    _ => positional.push(arg),
+                }
             }
 
-            import(PathBuf::from(directory_path), input_csv);
+            let [directory_path] = <[String; 1]>::try_from(positional).unwrap_or_else(|_| {
+                print_usage();
+                std::process::exit(1);
+            });
+
+            tui(PathBuf::from(directory_path), listing, import_options);
+        }
+        "undo" => {
+            let mut options = ImportOptions::default();
+            let mut positional = Vec::new();
+
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--dry-run" => options.dry_run = true,
+                    "--atomic" => options.atomic = true,
+                    "--strict" => options.strict = true,
+                    "--report" => options.report = Some(parse_flag_value(&mut args)),
+                    "--report-file" => {
+                        options.report_file = Some(PathBuf::from(flag_value(&mut args)))
+                    }
+                    "--journal" => options.journal = Some(PathBuf::from(flag_value(&mut args))),
+                    flag if flag.starts_with("--") => {
+                        print_usage();
+                        std::process::exit(1);
+                    }
+                    _ => positional.push(arg),
+                }
+            }
+
+            let mut positional = positional.into_iter();
+
+            let Some(journal_path) = positional.next() else {
+                print_usage();
+                std::process::exit(1);
+            };
+
+            if positional.next().is_some() {
+                print_usage();
+                std::process::exit(1);
+            }
+
+            undo(PathBuf::from(journal_path), options);
         }
         _ => {
             print_usage();
@@ -54,188 +286,796 @@ fn main() {
     }
 }
 
-fn export(directory_path: PathBuf, output_csv_path: PathBuf) {
-    let resolved_path = resolve_path(directory_path);
+#[derive(Default)]
+struct ImportOptions {
+    dry_run: bool,
+    atomic: bool,
+    strict: bool,
+    /// Import even if the directory changed since the CSV was exported.
+    force: bool,
+    /// Ask about each rename before running the plan.
+    interactive: bool,
+    /// Where the decisions of an interactive review are saved.
+    decisions: Option<PathBuf>,
+    /// Which columns of the CSV hold the names.
+    csv: CsvOptions,
+    /// How the plan is checked before it runs.
+    check: CheckOptions,
+    /// Remove folders that moves leave empty.
+    remove_empty_dirs: bool,
+    journal: Option<PathBuf>,
+    report: Option<ReportFormat>,
+    report_file: Option<PathBuf>,
+}
 
-    if !resolved_path.is_dir() {
-        eprintln!("Not a valid directory: {}", resolved_path.display());
-        std::process::exit(1);
+impl ImportOptions {
+    /// The report format to write, if any. `--report-file` on its own
+    /// implies JSON.
+    fn report_format(&self) -> Option<ReportFormat> {
+        match (self.report, &self.report_file) {
+            (Some(format), _) => Some(format),
+            (None, Some(_)) => Some(ReportFormat::Json),
+            (None, None) => None,
+        }
     }
 
-    let entries = match fs::read_dir(&resolved_path) {
-        Ok(entries) => entries,
-        Err(error) => {
-            eprintln!(
-                "Failed to read directory {}: {error}",
-                resolved_path.display()
-            );
-            std::process::exit(1);
+    /// Whether the report goes to stdout, which then carries nothing else.
+    fn report_to_stdout(&self) -> bool {
+        self.report_format().is_some() && self.report_file.is_none()
+    }
+}
+
+/// The `regex` command's options beyond those of import.
+struct RegexRun {
+    listing: ExportOptions,
+    options: RegexOptions,
+    output: Option<PathBuf>,
+}
+
+fn export(
+    directory_path: PathBuf,
+    output_csv_path: PathBuf,
+    options: ExportOptions,
+    template: Option<&str>,
+    columns: Columns,
+    dialect: Dialect,
+) {
+    let template = template.map(|template| {
+        template
+            .parse::<Template>()
+            .unwrap_or_else(|error| fail(error))
+    });
+
+    let resolved_path = resolve(&directory_path);
+    let mut listing = list(&resolved_path, &options);
+    listing.exclude(&resolve(&output_csv_path));
+
+    if let Some(template) = &template {
+        for (name, error) in
+            export::apply_template(&DiskFs, template, &resolved_path, &mut listing.entries)
+        {
+            eprintln!("Leaving new_name empty for {name}: {error}");
         }
-    };
+    }
 
-    let mut writer = match csv::Writer::from_path(&output_csv_path) {
-        Ok(writer) => writer,
-        Err(error) => {
-            eprintln!(
-                "Failed to create CSV {}: {error}",
-                output_csv_path.display()
+    export::write_csv(
+        &DiskFs,
+        &output_csv_path,
+        &listing.entries,
+        &listing.stamp,
+        &columns,
+        &dialect,
+    )
+    .unwrap_or_else(|error| fail(error));
+    warn_raw_names(&listing.entries);
+    println!(
+        "Wrote {}: {}",
+        file_kind(&output_csv_path),
+        output_csv_path.display()
+    );
+}
+
+fn import(directory_path: PathBuf, input_csv: PathBuf, options: ImportOptions) {
+    let resolved_directory = resolve(&directory_path);
+    let resolved_csv = resolve(&input_csv);
+
+    let CsvPlan { mut plan, stamp } =
+        import::read_plan(&DiskFs, &resolved_directory, &resolved_csv, &options.csv)
+            .unwrap_or_else(|error| fail(error));
+
+    if let Some(stamp) = stamp {
+        let staleness =
+            fingerprint::check(&DiskFs, &resolved_directory, &resolved_csv, &stamp, &plan)
+                .unwrap_or_else(|error| fail(error));
+        check_staleness(&resolved_directory, &staleness, options.force);
+    }
+
+    let decisions = options
+        .interactive
+        .then(|| review_plan(&resolved_directory, &mut plan, &options));
+    print_sanitized(plan::check(
+        &DiskFs,
+        &resolved_directory,
+        &mut plan,
+        &options.check,
+    ));
+    if let Some(decisions) = decisions {
+        save_decisions(&decisions, &plan, &options);
+    }
+    let cleanup = vacated_folders(&resolved_directory, options.remove_empty_dirs);
+
+    run_plan(&resolved_directory, &plan, cleanup, options);
+}
+
+/// Tells which new names the checks replaced with sanitized ones.
+fn print_sanitized(sanitized: Vec<plan::Sanitized>) {
+    for sanitized in sanitized {
+        eprintln!(
+            "Row {}: sanitized new_name {} to {}",
+            sanitized.row, sanitized.from, sanitized.to
+        );
+    }
+}
+
+/// Asks about each rename on the terminal, drops the declined ones, and
+/// returns the accepted ones for `save_decisions`.
+fn review_plan(
+    resolved_directory: &Path,
+    plan: &mut Vec<PlannedRename>,
+    options: &ImportOptions,
+) -> Vec<review::Decision> {
+    let declined = review::review(
+        &DiskFs,
+        resolved_directory,
+        plan,
+        &mut io::stdin().lock(),
+        &mut io::stderr(),
+        |plan| {
+            print_sanitized(plan::check_rows(
+                &DiskFs,
+                resolved_directory,
+                plan,
+                &options.check,
+            ))
+        },
+    )
+    .unwrap_or_else(|error| fail(Error::Review(error)));
+
+    eprintln!("Declined {declined} row(s).");
+    review::decisions(&DiskFs, resolved_directory, plan)
+}
+
+/// Saves the rows a review accepted that are still in the checked plan, as
+/// a CSV that replays the session.
+fn save_decisions(decisions: &[review::Decision], plan: &[PlannedRename], options: &ImportOptions) {
+    let decisions_path = options
+        .decisions
+        .clone()
+        .unwrap_or_else(review::default_decisions_path);
+    review::write_decisions(decisions, plan, &decisions_path).unwrap_or_else(|error| fail(error));
+
+    eprintln!(
+        "Wrote decisions: {}, import it to replay this review.",
+        decisions_path.display()
+    );
+}
+
+/// Reports what changed in the directory since export, and exits unless
+/// `--force` was given.
+fn check_staleness(resolved_directory: &Path, staleness: &Staleness, force: bool) {
+    if !staleness.stale {
+        return;
+    }
+
+    eprintln!(
+        "{} changed since the CSV was exported:",
+        resolved_directory.display()
+    );
+    for name in &staleness.changed {
+        eprintln!("  changed: {name}");
+    }
+    for name in &staleness.vanished {
+        eprintln!("  vanished: {name}");
+    }
+    for name in &staleness.unlisted {
+        eprintln!("  not in the CSV: {name} (added since export, or its row was removed)");
+    }
+    if staleness.changed.is_empty()
+        && staleness.vanished.is_empty()
+        && staleness.unlisted.is_empty()
+    {
+        eprintln!("  entries without a row in the CSV were modified or removed");
+    }
+
+    if !force {
+        eprintln!("Nothing was renamed, export again or use --force to import anyway.");
+        std::process::exit(EXIT_VALIDATION);
+    }
+}
+
+/// Lists the names in a directory that are not valid on `profile`, and
+/// exits with a validation error if there are any.
+fn check_names(directory_path: PathBuf, options: &ExportOptions, profile: Profile) {
+    let listing = list(&resolve(&directory_path), options);
+    let mut invalid = 0;
+
+    for entry in &listing.entries {
+        let file_name = entry.name.rsplit('/').next().unwrap_or(&entry.name);
+        if let Some(problem) = validate::check_name(file_name, profile) {
+            println!(
+                "{}: {problem} (could be {})",
+                entry.name,
+                validate::sanitize(file_name, profile)
             );
-            std::process::exit(1);
+            invalid += 1;
         }
-    };
+    }
 
-    if let Err(error) = writer.write_record(["old_name", "new_name"]) {
+    if invalid > 0 {
         eprintln!(
-            "Failed to write CSV header {}: {error}",
+            "{invalid} of {} name(s) are not valid on {profile}.",
+            listing.entries.len()
+        );
+        std::process::exit(EXIT_VALIDATION);
+    }
+    println!(
+        "All {} name(s) are valid on {profile}.",
+        listing.entries.len()
+    );
+}
+
+/// Reverses the renames recorded in a journal, newest first.
+fn undo(journal_path: PathBuf, options: ImportOptions) {
+    let resolved_journal = resolve(&journal_path);
+
+    let journaled =
+        journal::read_journal(&DiskFs, &resolved_journal).unwrap_or_else(|error| fail(error));
+    let mut plan = journal::undo_plan(journaled.renames);
+    plan::check_sequential(&DiskFs, &mut plan);
+
+    // Folders the import created are removed again once they are empty.
+    let mut cleanup = journaled.created;
+    cleanup.reverse();
+
+    run_plan(&resolved_journal, &plan, |_| cleanup, options);
+}
+
+/// Renames every entry whose name matches `pattern`, or writes the generated
+/// rows to a CSV for review when `--output` is given.
+fn regex_rename(
+    directory_path: PathBuf,
+    pattern: &str,
+    replacement: &str,
+    run: RegexRun,
+    import_options: ImportOptions,
+) {
+    let resolved_directory = resolve(&directory_path);
+    let mut listing = list(&resolved_directory, &run.listing);
+    if let Some(output_csv_path) = &run.output {
+        listing.exclude(&resolve(output_csv_path));
+    }
+
+    let listed = substitute::rename_matching(listing.entries, pattern, replacement, &run.options)
+        .unwrap_or_else(|error| fail(error));
+
+    if listed.is_empty() {
+        println!("No names match {pattern:?}");
+        return;
+    }
+
+    if let Some(output_csv_path) = run.output {
+        export::write_csv(
+            &DiskFs,
+            &output_csv_path,
+            &listed,
+            &listing.stamp,
+            &Columns::default(),
+            &Dialect::default(),
+        )
+        .unwrap_or_else(|error| fail(error));
+        warn_raw_names(&listed);
+        println!(
+            "Wrote {}: {}",
+            file_kind(&output_csv_path),
             output_csv_path.display()
         );
-        std::process::exit(1);
+        return;
     }
 
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_dir() {
-            let Some(folder_name_os) = path.file_name() else {
-                continue;
-            };
+    // Rows are numbered as they would be in the CSV written by --output.
+    run_listed(
+        &resolved_directory,
+        listed
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| (index + 2, entry)),
+        import_options,
+    );
+}
 
-            let folder_name = folder_name_os.to_string_lossy();
-            if let Err(error) = writer.write_record([folder_name.as_ref(), ""]) {
-                eprintln!(
-                    "Failed to write CSV row {}: {error}",
-                    output_csv_path.display()
-                );
-                std::process::exit(1);
-            }
-        }
+/// Lets the user rename entries by editing their names in `$VISUAL` or
+/// `$EDITOR`. Changed lines go through the same checks as a CSV import.
+fn edit(directory_path: PathBuf, listing: ExportOptions, import_options: ImportOptions) {
+    let resolved_directory = resolve(&directory_path);
+    let listed = list(&resolved_directory, &listing).entries;
+
+    if listed.is_empty() {
+        println!("Nothing to edit in {}", resolved_directory.display());
+        return;
     }
 
-    if let Err(error) = writer.flush() {
-        eprintln!("Failed to flush CSV {}: {error}", output_csv_path.display());
-        std::process::exit(1);
+    let renames = edit::edit_names(listed).unwrap_or_else(|error| fail(error));
+
+    if renames.is_empty() {
+        println!("No names changed.");
+        return;
     }
 
-    println!("Wrote CSV: {}", output_csv_path.display());
+    run_listed(&resolved_directory, renames, import_options);
 }
 
-fn import(directory_path: PathBuf, input_csv: PathBuf) {
-    let resolved_directory = resolve_path(directory_path);
+/// Lets the user rename entries in a terminal UI that checks new names as
+/// they are typed, then applies them with the same checks as import.
+fn tui(directory_path: PathBuf, listing: ExportOptions, import_options: ImportOptions) {
+    let resolved_directory = resolve(&directory_path);
+    let listed = list(&resolved_directory, &listing).entries;
 
-    if !resolved_directory.is_dir() {
-        eprintln!("Not a valid directory: {}", resolved_directory.display());
-        std::process::exit(1);
+    if listed.is_empty() {
+        println!("Nothing to rename in {}", resolved_directory.display());
+        return;
     }
 
-    let resolved_csv = resolve_path(input_csv);
+    // Nothing may be printed while the UI is up, so sanitized names are only
+    // shown in the table until the plan is applied.
+    let mut check = |plan: &mut [PlannedRename]| {
+        plan::check_rows(&DiskFs, &resolved_directory, plan, &import_options.check);
+    };
+    let session = Session::new(resolved_directory.clone(), listed, &mut check);
 
-    if !resolved_csv.is_file() {
-        eprintln!("Not a valid CSV file: {}", resolved_csv.display());
-        std::process::exit(1);
+    match tui::run(session, check).unwrap_or_else(|error| fail(error)) {
+        Some(renames) => run_listed(&resolved_directory, renames, import_options),
+        None => println!("Nothing was renamed."),
     }
+}
 
-    let mut reader = match csv::Reader::from_path(&resolved_csv) {
-        Ok(reader) => reader,
-        Err(error) => {
-            eprintln!("Failed to read CSV {}: {error}", resolved_csv.display());
-            std::process::exit(1);
+/// Checks and applies renames generated from a directory scan instead of read
+/// from a CSV, numbered by `row`.
+fn run_listed(
+    resolved_directory: &Path,
+    renames: impl IntoIterator<Item = (usize, ListedEntry)>,
+    options: ImportOptions,
+) {
+    let mut plan = plan::from_listed(resolved_directory, renames);
+    print_sanitized(plan::check(
+        &DiskFs,
+        resolved_directory,
+        &mut plan,
+        &options.check,
+    ));
+    let cleanup = vacated_folders(resolved_directory, options.remove_empty_dirs);
+
+    run_plan(resolved_directory, &plan, cleanup, options);
+}
+
+/// The folders to remove if the renames that ran leave them empty, when
+/// `--remove-empty-dirs` asks for that.
+fn vacated_folders(
+    resolved_directory: &Path,
+    remove_empty_dirs: bool,
+) -> impl FnOnce(&[&PlannedRename]) -> Vec<PathBuf> {
+    move |renamed| {
+        if remove_empty_dirs {
+            plan::vacated_folders(resolved_directory, renamed.iter().copied())
+        } else {
+            Vec::new()
         }
+    }
+}
+
+/// Prints or applies a checked plan according to the options shared by
+/// import, undo and the commands that generate renames, then removes those
+/// of the folders `cleanup` picks from the renames that ran which the run
+/// left empty.
+fn run_plan(
+    source: &Path,
+    plan: &[PlannedRename],
+    cleanup: impl FnOnce(&[&PlannedRename]) -> Vec<PathBuf>,
+    options: ImportOptions,
+) {
+    let report_format = options.report_format();
+    let quiet = options.report_to_stdout();
+    let mut report = Report::new(source, options.dry_run);
+    let write_report = |report: &Report| match (report_format, &options.report_file) {
+        (Some(format), Some(report_path)) => report
+            .write(format, report_path)
+            .unwrap_or_else(|error| fail(error)),
+        (Some(format), None) => println!("{}", report.render(format)),
+        (None, _) => {}
     };
 
-    let headers = match reader.headers() {
-        Ok(headers) => headers.clone(),
-        Err(error) => {
-            eprintln!(
-                "Failed to read CSV headers {}: {error}",
-                resolved_csv.display()
-            );
-            std::process::exit(1);
+    if options.dry_run {
+        if report_format.is_some() {
+            for rename in plan {
+                report.record_planned(rename);
+            }
+            write_report(&report);
+        } else {
+            print_plan(source, plan);
         }
-    };
 
-    if headers.get(0) != Some("old_name") || headers.get(1) != Some("new_name") {
-        eprintln!(
-            "Invalid CSV headers in {}. Expected: old_name,new_name",
-            resolved_csv.display()
-        );
-        std::process::exit(1);
+        if plan
+            .iter()
+            .any(|rename| rename.skip.is_some() && rename.is_requested())
+        {
+            std::process::exit(EXIT_VALIDATION);
+        }
+        return;
     }
 
-    for (index, result) in reader.records().enumerate() {
-        let record = match result {
-            Ok(record) => record,
-            Err(error) => {
-                eprintln!("Failed to read CSV row {}: {error}", index + 2);
-                continue;
+    let stop = if options.atomic {
+        StopPolicy::RollBack
+    } else if options.strict {
+        StopPolicy::Stop
+    } else {
+        StopPolicy::Continue
+    };
+
+    let rejected = stop.rejected(plan);
+    if !rejected.is_empty() {
+        for rename in rejected {
+            if let Some(reason) = &rename.skip {
+                eprintln!("Row {} would be skipped: {reason}", rename.row);
+                report.record(rename, &Outcome::skipped(reason));
             }
-        };
+        }
+        eprintln!("Run aborted before renaming anything.");
+        write_report(&report);
+        std::process::exit(EXIT_VALIDATION);
+    }
 
-        let old_name = record.get(0).unwrap_or("").trim();
-        let new_name = record.get(1).unwrap_or("").trim();
+    let journal_path = options
+        .journal
+        .clone()
+        .unwrap_or_else(journal::default_journal_path);
+    let mut journal = Journal::create(&resolve(&journal_path)).unwrap_or_else(|error| fail(error));
+    report.set_journal(journal.path());
 
-        if old_name.is_empty() || new_name.is_empty() {
-            eprintln!("Skipping row {}: empty old_name or new_name", index + 2);
-            continue;
-        }
+    let mut renamed = 0;
+    let execution = apply::execute(
+        &DiskFs,
+        plan,
+        Some(&mut journal),
+        stop,
+        |rename, outcome| {
+            print_outcome(rename, outcome, quiet);
+            report.record(rename, outcome);
 
-        let old_path = resolved_directory.join(old_name);
-        let new_path = resolved_directory.join(new_name);
+            match outcome {
+                Outcome::Renamed => renamed += 1,
+                Outcome::Skipped { .. } if !rename.is_requested() => {}
+                Outcome::Skipped { .. } | Outcome::Failed(_) => {
+                    if let StopPolicy::RollBack = stop {
+                        eprintln!(
+                            "Row {} did not complete, rolling back {renamed} rename(s)",
+                            rename.row
+                        );
+                    }
+                }
+                Outcome::RolledBack | Outcome::RollbackFailed(_) => {}
+            }
+        },
+    )
+    .unwrap_or_else(|error| fail(error));
 
-        if !old_path.is_dir() {
+    match (&execution.rollback, execution.stopped_at) {
+        (Some(rollback), _) if rollback.stuck.is_empty() => {
+            eprintln!("Rollback complete: every rename from this run was reversed.");
+        }
+        (Some(rollback), _) => {
             eprintln!(
-                "Skipping row {}: source folder does not exist: {}",
-                index + 2,
-                old_path.display()
+                "Rollback incomplete: {} rename(s) could not be reversed:",
+                rollback.stuck.len()
             );
-            continue;
+            for reverse in &rollback.stuck {
+                eprintln!("  {} is still at {}", reverse.new_name, reverse.old_name);
+            }
+        }
+        (None, Some(row)) => {
+            eprintln!("Stopping at row {row}, later rows were not attempted.");
         }
+        (None, None) => {}
+    }
 
-        if new_path.exists() {
-            eprintln!(
-                "Skipping row {}: target already exists: {}",
-                index + 2,
-                new_path.display()
-            );
-            continue;
+    let renamed: Vec<&PlannedRename> = execution.renamed.iter().map(|&i| &plan[i]).collect();
+    let removed = apply::remove_empty_folders(&DiskFs, &cleanup(&renamed), Some(&mut journal))
+        .unwrap_or_else(|error| fail(error));
+    if !quiet {
+        for folder in removed {
+            println!("Removed empty folder: {}", folder.display());
         }
+        println!("Wrote journal: {}", journal.path().display());
+    }
 
-        if let Err(error) = fs::rename(&old_path, &new_path) {
-            eprintln!(
-                "Failed to rename row {} ({} -> {}): {error}",
-                index + 2,
-                old_name,
-                new_name
-            );
-            continue;
+    write_report(&report);
+
+    let counts = report.counts();
+    if counts.failed > 0 || counts.rolled_back > 0 {
+        std::process::exit(EXIT_IO);
+    } else if counts.skipped > 0 && counts.renamed > 0 {
+        std::process::exit(EXIT_PARTIAL);
+    } else if counts.skipped > 0 {
+        std::process::exit(EXIT_VALIDATION);
+    }
+}
+
+fn print_outcome(rename: &PlannedRename, outcome: &Outcome, quiet: bool) {
+    match outcome {
+        Outcome::Renamed if !quiet => {
+            println!("Renamed: {} -> {}", rename.old_name, rename.new_name)
         }
+        Outcome::RolledBack if !quiet => {
+            println!("Rolled back: {} -> {}", rename.old_name, rename.new_name)
+        }
+        Outcome::Renamed | Outcome::RolledBack => {}
+        Outcome::Skipped { message, .. } => eprintln!("Skipping row {}: {message}", rename.row),
+        Outcome::Failed(error) => eprintln!(
+            "Failed to rename row {} ({} -> {}): {error}",
+            rename.row, rename.old_name, rename.new_name
+        ),
+        Outcome::RollbackFailed(error) => eprintln!(
+            "Failed to roll back row {} ({} -> {}): {error}",
+            rename.row, rename.old_name, rename.new_name
+        ),
+    }
+}
+
+/// Lists the names a CSV could only keep exactly in its raw column.
+fn warn_raw_names(listed: &[ListedEntry]) {
+    let raw: Vec<&ListedEntry> = listed
+        .iter()
+        .filter(|entry| entry.raw_name.is_some())
+        .collect();
+
+    if raw.is_empty() {
+        return;
+    }
 
-        println!("Renamed: {} -> {}", old_name, new_name);
+    eprintln!(
+        "Warning: {} name(s) are not valid UTF-8, old_name shows them with replacement characters and old_name_raw keeps them exactly:",
+        raw.len()
+    );
+    for entry in raw {
+        eprintln!("  {}", entry.raw_name.as_deref().unwrap_or(""));
     }
 }
 
-fn resolve_path(path: PathBuf) -> PathBuf {
-    if path.is_absolute() {
-        path
+/// What a plan was written as, for the confirmation.
+fn file_kind(path: &Path) -> &'static str {
+    if spreadsheet::Format::of(path).is_some() {
+        "spreadsheet"
     } else {
-        match env::current_dir() {
-            Ok(current_dir) => current_dir.join(path),
-            Err(error) => {
-                eprintln!("Failed to get current directory: {error}");
+        "CSV"
+    }
+}
+
+/// Scans a directory, warning about nested folders that could not be read.
+fn list(resolved_directory: &Path, options: &ExportOptions) -> Listing {
+    let listing = export::list_entries(&DiskFs, resolved_directory, options)
+        .unwrap_or_else(|error| fail(error));
+
+    for (path, error) in &listing.unreadable {
+        eprintln!("Skipping contents of {}: {error}", path.display());
+    }
+
+    listing
+}
+
+fn print_plan(source: &Path, plan: &[PlannedRename]) {
+    println!("Dry run for {} (nothing will be renamed)", source.display());
+
+    for rename in plan {
+        match &rename.skip {
+            None => println!(
+                "  row {}: {} -> {} [rename]",
+                rename.row, rename.old_name, rename.new_name
+            ),
+            Some(reason) => println!(
+                "  row {}: {} -> {} [skip: {reason}]",
+                rename.row, rename.old_name, rename.new_name
+            ),
+        }
+    }
+
+    // Rows broken up by a temporary name appear more than once in the plan.
+    let rows: HashSet<usize> = plan.iter().map(|rename| rename.row).collect();
+    let skipped = plan.iter().filter(|rename| rename.skip.is_some()).count();
+    println!(
+        "Summary: {} rows, {} would be renamed, {} would be skipped",
+        rows.len(),
+        rows.len() - skipped,
+        skipped
+    );
+}
+
+/// Parses a flag of the checks and run shared by import and the commands that
+/// generate renames, returning whether `arg` was one.
+fn parse_import_flag(
+    arg: &str,
+    args: &mut impl Iterator<Item = String>,
+    options: &mut ImportOptions,
+) -> bool {
+    match arg {
+        "--dry-run" => options.dry_run = true,
+        "--atomic" => options.atomic = true,
+        "--strict" => options.strict = true,
+        "--nfc" => options.check.nfc = true,
+        "--profile" => options.check.profile = parse_flag_value(args),
+        "--sanitize" => options.check.sanitize = true,
+        "--create-parents" => options.check.create_parents = true,
+        "--remove-empty-dirs" => options.remove_empty_dirs = true,
+        "--type" => options.check.type_filter = Some(parse_flag_value(args)),
+        "--report" => options.report = Some(parse_flag_value(args)),
+        "--report-file" => options.report_file = Some(PathBuf::from(flag_value(args))),
+        "--journal" => options.journal = Some(PathBuf::from(flag_value(args))),
+        _ => return false,
+    }
+    true
+}
+
+/// Parses a flag of the commands that list a directory and rename what they
+/// generate from it, returning whether `arg` was one. `--type` applies to
+/// both the listing and the renames.
+fn parse_run_flag(
+    arg: &str,
+    args: &mut impl Iterator<Item = String>,
+    listing: &mut ExportOptions,
+    options: &mut ImportOptions,
+) -> bool {
+    match arg {
+        "--recursive" => listing.recursive = true,
+        "--max-depth" => listing.max_depth = Some(parse_flag_value(args)),
+        "--type" => {
+            let type_filter = parse_flag_value(args);
+            listing.type_filter = type_filter;
+            options.check.type_filter = Some(type_filter);
+        }
+        _ => return parse_import_flag(arg, args, options),
+    }
+    true
+}
+
+fn flag_value(args: &mut impl Iterator<Item = String>) -> String {
+    let Some(value) = args.next() else {
+        print_usage();
+        std::process::exit(1);
+    };
+    value
+}
+
+fn parse_flag_value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>) -> T {
+    let Ok(value) = flag_value(args).parse() else {
+        print_usage();
+        std::process::exit(1);
+    };
+    value
+}
+
+/// The columns named by `--columns`, separated by commas, or every column
+/// for `all`.
+fn parse_columns(value: &str) -> Vec<Column> {
+    if value == "all" {
+        return Column::ALL.to_vec();
+    }
+
+    value
+        .split(',')
+        .map(|name| {
+            name.trim().parse().unwrap_or_else(|()| {
+                print_usage();
                 std::process::exit(1);
-            }
+            })
+        })
+        .collect()
+}
+
+/// The single ASCII character given to `--delimiter` or `--quote`, where
+/// `tab` or `\t` stands for a tab.
+fn parse_csv_char(value: &str) -> u8 {
+    match value {
+        "tab" | "\\t" => b'\t',
+        _ if value.len() == 1 && value.is_ascii() => value.as_bytes()[0],
+        _ => {
+            print_usage();
+            std::process::exit(1);
         }
     }
 }
 
+fn resolve(path: &Path) -> PathBuf {
+    resolve_path(path).unwrap_or_else(|error| fail(error))
+}
+
+/// Prints the error and exits. Invalid CSV headers count as a validation
+/// failure, everything else as a setup error.
+fn fail(error: Error) -> ! {
+    eprintln!("{error}");
+
+    match error {
+        Error::InvalidHeaders { .. } | Error::InvalidFingerprint { .. } => {
+            std::process::exit(EXIT_VALIDATION)
+        }
+        Error::InvalidEdit(_) => {
+            eprintln!("Nothing was renamed.");
+            std::process::exit(1);
+        }
+        _ => std::process::exit(1),
+    }
+}
+
 fn print_usage() {
     eprintln!(
-        "There are 3 commands, use export to generate a CSV of the current folder names, and import to rename folders based on a CSV.\n\
-The intermediate CSV file should have 2 columns: old_name,new_name\n\
+        "There are 7 commands, use export to generate a CSV of the current folder names, import to rename folders based on a CSV, regex, edit or tui to rename without a CSV, and undo to reverse an import.\n\
+The intermediate CSV file should have 2 columns: old_name,new_name, in any order\n\
+An optional type column (dir or file), as written by export, makes import check each entry is still the same kind.\n\
+Names that are not valid UTF-8 are also written escaped (e.g. caf%E9) in an old_name_raw column, which import uses to find the exact entry.\n\
+Export also writes a fingerprint column, which lets import detect entries that changed, vanished or appeared since the export.\n\
+Export, import and regex --output read and write .xlsx and .ods spreadsheets directly instead when the file name ends in one of those, with names kept as text (no lost leading zeros or dates) and the --columns values as numbers and dates.\n\
 \n\
-rename_tool export <directory_path> [output_csv]\n\
- - Exports the folder names to a CSV file. If output_csv is not provided, it defaults to folders.csv in the current directory.\n\
+rename_tool export [--recursive] [--max-depth <n>] [--type dirs|files|all] [--template <template>] [--columns <columns>] [--excel] [--delimiter <char>] [--quote <char>] <directory_path> [output_csv]\n\
+rename_tool export --check [--profile posix|windows|macos|portable] [--recursive] [--max-depth <n>] [--type dirs|files|all] <directory_path>\n\
+ - Exports the folder names to a CSV file. If output_csv is not provided, it defaults to folders.csv in the current directory. The CSV itself is never listed.\n\
+ - --type chooses whether folders, files or both are listed. Defaults to dirs.\n\
+ - --recursive also lists nested folders as relative paths (e.g. clients/acme/2023), down to --max-depth levels if given.\n\
+ - --check lists the names that are not valid on --profile, with a suggested replacement, instead of writing a CSV. Exits with 3 if there are any.\n\
+ - --columns adds read-only columns after type, to sort and choose names in a spreadsheet: a comma-separated list of modified, created (UTC), size (in bytes, of everything inside for folders), items (entries inside a folder, at any depth), owner, group and permissions, or all. Import ignores them.\n\
+ - --excel writes a CSV that Excel opens correctly with a double-click: UTF-8 with a byte order mark, \\r\\n line ends, and ; as the delimiter where the locale (LC_ALL, LC_NUMERIC or LANG) writes decimals with a comma. The locale is a best guess at Excel's list separator; use --delimiter if it differs.\n\
+ - --delimiter and --quote choose other characters than , and \" (tab for a tab), also with --excel. These three only apply to CSV files.\n\
+ - --template pre-fills new_name, e.g. \"{{counter:3}}_{{parent|lower}}_{{name}}\". Placeholders: {{name}}, {{stem}}, {{ext}}, {{parent}}, {{counter:<width>}}, {{modified:<format>}} and {{created:<format>}} (UTC, format like %Y-%m-%d). Add |upper, |lower or |title to change case.\n\
 \n\
-rename_tool import <directory_path> [input_csv]\n\
+rename_tool import [--dry-run] [--interactive [--decisions <csv>]] [--old-col <column>] [--new-col <column>] [--no-header] [--delimiter <char>] [--quote <char>] [--atomic] [--strict] [--force] [--allow-move-outside] [--nfc] [--profile posix|windows|macos|portable] [--sanitize] [--create-parents] [--remove-empty-dirs] [--type dirs|files|all] [--journal <journal_csv>] [--report json] [--report-file <path>] <directory_path> [input_csv]\n\
  - Imports the folder names from a CSV file and renames the folders accordingly. If input_csv is not provided, it defaults to folders.csv in the current directory.\n\
+ - Columns are found by their header wherever they are, ignoring case, spaces and a byte order mark (Old Name finds old_name), and other columns are ignored.\n\
+ - The CSV may be UTF-8, UTF-16 with a byte order mark, or Windows-1252, and its delimiter (, ; tab or |) is detected from the first line unless --delimiter is given. --quote sets the quote character. A spreadsheet is read from its first sheet.\n\
+ - --old-col and --new-col choose other columns for the names, by header or by number counted from 1. --no-header reads the first row as a rename, with the names in columns 1 and 2 unless given by number.\n\
+ - Names may be relative paths as written by export --recursive, and nested folders are renamed before their parents. Paths in new_name refer to folders as they are before the import.\n\
+ - A new_name in another folder moves the entry there (2023_acme_report -> 2023/acme/report). --create-parents creates target folders that do not exist yet, and removes them again if the run is rolled back.\n\
+ - --remove-empty-dirs removes the folders that moves leave empty.\n\
+ - Rows whose old_name or new_name lead outside directory_path, through .., an absolute path or a symlinked folder, are skipped unless --allow-move-outside is given.\n\
+ - Rows are applied as one mapping, so swaps (a -> b, b -> a) and rotations work regardless of row order.\n\
+ - Renames that only change case or Unicode normalization (Project -> project) work on case-insensitive filesystems too; they go through a temporary name.\n\
+ - --nfc normalizes every new name to Unicode NFC first, so a row whose new_name repeats an NFD old_name converts it.\n\
+ - Every new name is checked against --profile, which defaults to the current platform: reserved names (., .., CON, NUL), forbidden characters (NUL, :, \\, ...), trailing dots or spaces on Windows, and names over 255 bytes. portable combines every profile's rules.\n\
+ - Rows with invalid new names are skipped, or with --sanitize renamed to a valid version of the name (forbidden characters become _).\n\
+ - --type chooses whether folders, files or both may be renamed. Without it, rows with a type column value are renamed whatever their type, and the rest only if they are folders.\n\
+ - --dry-run checks every row and prints the rename plan without touching disk. Exits non-zero if any row would be skipped.\n\
+ - --interactive shows each rename that passed its checks with the entry's size, item count and modification time, and asks to accept it, skip it, edit the new name, accept all remaining rows or quit (skipping the rest).\n\
+   The accepted rows are saved to --decisions, by default rename_decisions_<timestamp>.csv, which import can replay without asking.\n\
+ - Refuses to run if the directory changed since the CSV was exported, unless --force is given.\n\
+ - --atomic refuses to start unless every row passes its checks, and reverses every rename already made if one fails.\n\
+ - --strict refuses to start if any row fails its checks, and stops at the first rename that fails without reversing earlier ones.\n\
+ - Every run writes a journal of the renames it performed, by default rename_journal_<timestamp>.csv in the current directory.\n\
+ - --report json prints a JSON report of every row's outcome and reason code instead of the usual output, or writes it to --report-file.\n\
+\n\
+rename_tool regex [-i] [--all] [--filter <pattern>] [--output <csv>] [export and import options] <directory_path> <pattern> <replacement>\n\
+ - Renames every entry whose name matches pattern, replacing the first match (every match with --all) with replacement. Capture groups are written $1 or ${{name}}.\n\
+ - -i matches case-insensitively, and --filter only considers entries whose relative path also matches its pattern.\n\
+ - Accepts export's --recursive, --max-depth and --type to choose entries, and import's --dry-run, --atomic, --strict, --nfc, --profile, --sanitize, --create-parents, --remove-empty-dirs, --journal and --report options.\n\
+ - --output writes the generated old_name,new_name CSV for review instead of renaming anything.\n\
+\n\
+rename_tool edit [export and import options] <directory_path>\n\
+ - Opens the names in $VISUAL or $EDITOR, one numbered line per entry, and renames whatever was changed on save, with the same checks as import.\n\
+ - Nothing is renamed if lines were added, removed or reordered.\n\
+ - Accepts export's --recursive, --max-depth and --type to choose entries, and import's --dry-run, --atomic, --strict, --nfc, --profile, --sanitize, --create-parents, --remove-empty-dirs, --journal and --report options.\n\
+\n\
+rename_tool tui [export and import options] <directory_path>\n\
+ - Lists the entries in a table where new names can be typed in place, with duplicates, existing targets, invalid names and other problems shown as they are typed.\n\
+ - Enter edits the selected new name, x clears it, / filters by name, r replaces a regex in the shown names, s changes the sort order and a applies the new names with the same checks as import.\n\
+ - Accepts export's --recursive, --max-depth and --type to choose entries, and import's --dry-run, --atomic, --strict, --nfc, --profile, --sanitize, --create-parents, --remove-empty-dirs, --journal and --report options.\n\
+\n\
+rename_tool undo [--dry-run] [--atomic] [--strict] [--journal <journal_csv>] [--report json] [--report-file <path>] <journal_csv>\n\
+ - Reverses the renames recorded in a journal, newest first, with the same checks as import. Folders the import created are removed if the undo leaves them empty, and folders it removed are created again. The undo run writes its own journal.\n\
+\n\
+Exit codes for import, undo, regex, edit and tui: 0 every row was renamed, 1 usage or setup error, 2 some rows were renamed and some skipped, 3 nothing was renamed because rows failed their checks (or a dry run found problems), 4 a rename failed or was rolled back. Rows left with an empty new_name, or the name they have, are reported as unchanged and never count as skipped.\n\
 \n\
 rename_tool help\n\
  - Displays this help message."
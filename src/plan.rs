@@ -0,0 +1,946 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+use crate::entry::{EntryKind, TypeFilter};
+use crate::export::ListedEntry;
+use crate::filesystem::FileSystem;
+use crate::fingerprint::Fingerprint;
+use crate::validate::{self, Problem, Profile};
+
+pub struct PlannedRename {
+    pub row: usize,
+    pub old_name: String,
+    pub new_name: String,
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    /// The kind the CSV recorded for this entry, if it has a `type` column.
+    pub expected_kind: Option<EntryKind>,
+    /// The entry's fingerprint when it was exported, if the CSV recorded one.
+    pub fingerprint: Option<Fingerprint>,
+    /// Whether missing folders above `new_path` are created before renaming.
+    pub create_parents: bool,
+    pub skip: Option<SkipReason>,
+}
+
+impl PlannedRename {
+    pub fn new(
+        row: usize,
+        old_name: String,
+        new_name: String,
+        old_path: PathBuf,
+        new_path: PathBuf,
+    ) -> PlannedRename {
+        PlannedRename {
+            row,
+            old_name,
+            new_name,
+            old_path,
+            new_path,
+            expected_kind: None,
+            fingerprint: None,
+            create_parents: false,
+            skip: None,
+        }
+    }
+
+    /// Whether the row asks for a rename at all. Rows whose new_name was left
+    /// empty, or names the entry as it is, do not: they are skipped without
+    /// being a problem.
+    pub fn is_requested(&self) -> bool {
+        !self.new_name.is_empty() && !matches!(self.skip, Some(SkipReason::Unchanged))
+    }
+
+    /// The rename that puts this one back.
+    pub fn reversed(&self) -> PlannedRename {
+        PlannedRename::new(
+            self.row,
+            self.new_name.clone(),
+            self.old_name.clone(),
+            self.new_path.clone(),
+            self.old_path.clone(),
+        )
+    }
+}
+
+pub enum SkipReason {
+    UnreadableRow(csv::Error),
+    EmptyName,
+    InvalidType(String),
+    Unchanged,
+    DuplicateSource(usize),
+    DuplicateTarget(usize),
+    IntoItself,
+    MissingParent(PathBuf),
+    CircularMove,
+    InvalidRawName(String),
+    InvalidName(Profile, Problem),
+    OutsideDirectory(PathBuf),
+    MissingSource(PathBuf),
+    KindChanged(EntryKind, EntryKind),
+    ExcludedKind(EntryKind),
+    TargetExists(PathBuf),
+}
+
+impl SkipReason {
+    /// A stable identifier for reports.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SkipReason::UnreadableRow(_) => "unreadable_row",
+            SkipReason::EmptyName => "empty_name",
+            SkipReason::InvalidType(_) => "invalid_type",
+            SkipReason::Unchanged => "unchanged",
+            SkipReason::DuplicateSource(_) => "duplicate_source",
+            SkipReason::DuplicateTarget(_) => "duplicate_target",
+            SkipReason::IntoItself => "into_itself",
+            SkipReason::MissingParent(_) => "missing_parent",
+            SkipReason::CircularMove => "circular_move",
+            SkipReason::InvalidRawName(_) => "invalid_raw_name",
+            SkipReason::InvalidName(_, _) => "invalid_name",
+            SkipReason::OutsideDirectory(_) => "outside_directory",
+            SkipReason::MissingSource(_) => "missing_source",
+            SkipReason::KindChanged(_, _) => "kind_changed",
+            SkipReason::ExcludedKind(_) => "excluded_kind",
+            SkipReason::TargetExists(_) => "target_exists",
+        }
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UnreadableRow(error) => write!(f, "failed to read row: {error}"),
+            SkipReason::EmptyName => write!(f, "empty old_name or new_name"),
+            SkipReason::InvalidType(value) => {
+                write!(f, "unknown type {value:?}, expected dir or file")
+            }
+            SkipReason::Unchanged => write!(f, "new_name is the same as old_name"),
+            SkipReason::DuplicateSource(row) => {
+                write!(f, "source is already renamed by row {row}")
+            }
+            SkipReason::DuplicateTarget(row) => {
+                write!(f, "target is already claimed by row {row}")
+            }
+            SkipReason::IntoItself => write!(f, "cannot move a folder into itself"),
+            SkipReason::MissingParent(path) => write!(
+                f,
+                "target folder does not exist: {}, use --create-parents to create it",
+                path.display()
+            ),
+            SkipReason::CircularMove => write!(
+                f,
+                "moves into a folder that other rows rename or move, which no order of the rows allows"
+            ),
+            SkipReason::InvalidRawName(raw_name) => {
+                write!(f, "old_name_raw is not a valid escaped name: {raw_name}")
+            }
+            SkipReason::InvalidName(profile, problem) => {
+                write!(f, "new_name is not valid on {profile}: {problem}")
+            }
+            SkipReason::OutsideDirectory(path) => {
+                write!(
+                    f,
+                    "{} is outside the directory, use --allow-move-outside to rename it anyway",
+                    path.display()
+                )
+            }
+            SkipReason::MissingSource(path) => {
+                write!(f, "source does not exist: {}", path.display())
+            }
+            SkipReason::KindChanged(expected, found) => {
+                write!(
+                    f,
+                    "source was exported as a {expected} but is now a {found}"
+                )
+            }
+            SkipReason::ExcludedKind(kind) => {
+                write!(f, "source is a {kind}, which --type does not include")
+            }
+            SkipReason::TargetExists(path) => {
+                write!(f, "target already exists: {}", path.display())
+            }
+        }
+    }
+}
+
+/// Which of the checks of `check` run, and how.
+#[derive(Clone, Copy, Default)]
+pub struct CheckOptions {
+    /// Keep rows whose source or target is outside the directory.
+    pub allow_move_outside: bool,
+    /// Normalize new names to NFC.
+    pub nfc: bool,
+    /// The filesystem rules new names must follow.
+    pub profile: Profile,
+    /// Replace invalid new names instead of skipping their rows.
+    pub sanitize: bool,
+    /// Create missing folders that rows move entries into.
+    pub create_parents: bool,
+    /// The kinds of entries to rename. Without one, rows that name their
+    /// type are renamed whatever it is, and the rest only if they are
+    /// folders.
+    pub type_filter: Option<TypeFilter>,
+}
+
+/// Checks a plan for `directory` as a whole and orders it to run, returning
+/// the new names it sanitized. This is `check_rows` followed by `order_plan`.
+pub fn check(
+    fs: &impl FileSystem,
+    directory: &Path,
+    plan: &mut Vec<PlannedRename>,
+    options: &CheckOptions,
+) -> Vec<Sanitized> {
+    let sanitized = check_rows(fs, directory, plan, options);
+    *plan = order_plan(fs, std::mem::take(plan));
+    sanitized
+}
+
+/// The checks of `check` that leave the rows in place, which may be run
+/// again as rows are edited or dropped: `check_contained` unless moves
+/// outside are allowed, `normalize_new_names` if asked for,
+/// `validate_new_names` and `check_simultaneous`.
+pub fn check_rows(
+    fs: &impl FileSystem,
+    directory: &Path,
+    plan: &mut [PlannedRename],
+    options: &CheckOptions,
+) -> Vec<Sanitized> {
+    for rename in plan.iter_mut() {
+        rename.create_parents = options.create_parents;
+    }
+    if !options.allow_move_outside {
+        check_contained(fs, directory, plan);
+    }
+    if options.nfc {
+        normalize_new_names(plan);
+    }
+    let sanitized = validate_new_names(fs, directory, plan, options.profile, options.sanitize);
+    check_simultaneous(fs, plan, options.type_filter);
+
+    sanitized
+}
+
+/// Runs every rename check without touching disk.
+///
+/// Rows are checked in order against the directory as it will look after the
+/// earlier rows have been applied, so a row may target a name an earlier row
+/// frees up, may refer to a folder inside one an earlier row moved, and two
+/// rows may not claim the same target.
+pub fn check_sequential(fs: &impl FileSystem, plan: &mut [PlannedRename]) {
+    let mut moves: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    for rename in plan.iter_mut().filter(|rename| rename.skip.is_none()) {
+        let source_present =
+            origin_of(&rename.old_path, &moves).is_some_and(|origin| fs.exists(&origin));
+        // On a case-insensitive filesystem the target may be another
+        // spelling of an entry an earlier row moved away.
+        let target_taken = origin_of(&rename.new_path, &moves).is_some_and(|origin| {
+            fs.exists(&origin) && !moves.iter().any(|(from, _)| fs.same_entry(&origin, from))
+        });
+
+        if !source_present {
+            rename.skip = Some(SkipReason::MissingSource(rename.old_path.clone()));
+        } else if target_taken {
+            rename.skip = Some(SkipReason::TargetExists(rename.new_path.clone()));
+        } else {
+            moves.push((rename.old_path.clone(), rename.new_path.clone()));
+        }
+    }
+}
+
+/// Where whatever is at `path` after `moves` have run is found on disk today,
+/// or `None` if the moves leave nothing there.
+fn origin_of(path: &Path, moves: &[(PathBuf, PathBuf)]) -> Option<PathBuf> {
+    let mut path = path.to_path_buf();
+
+    for (from, to) in moves.iter().rev() {
+        if let Ok(rest) = path.strip_prefix(to) {
+            path = from.join(rest);
+        } else if path.starts_with(from) {
+            return None;
+        }
+    }
+
+    Some(path)
+}
+
+/// Whether the rename only changes the case or Unicode normalization of a
+/// name that the filesystem already finds under the new spelling, as case-
+/// and normalization-insensitive filesystems do.
+pub fn renames_in_place(fs: &impl FileSystem, rename: &PlannedRename) -> bool {
+    let (Some(old), Some(new)) = (rename.old_path.file_name(), rename.new_path.file_name()) else {
+        return false;
+    };
+
+    // Hard links to one file are the same entry too, but under unrelated
+    // names.
+    fold(old) == fold(new) && fs.same_entry(&rename.old_path, &rename.new_path)
+}
+
+fn fold(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase().nfc().collect()
+}
+
+/// Skips every row whose source or target is not inside `directory` once
+/// `..` and symlinks are resolved, as with `../elsewhere` or an absolute
+/// path. Renaming a symlink that lives inside the directory is fine, wherever
+/// it points.
+pub fn check_contained(fs: &impl FileSystem, directory: &Path, plan: &mut [PlannedRename]) {
+    let directory = real_path(fs, directory);
+
+    for rename in plan.iter_mut().filter(|rename| rename.skip.is_none()) {
+        let outside = [&rename.old_path, &rename.new_path]
+            .into_iter()
+            .map(|path| real_entry_path(fs, path))
+            .find(|path| path == &directory || !path.starts_with(&directory));
+
+        if let Some(path) = outside {
+            rename.skip = Some(SkipReason::OutsideDirectory(path));
+        }
+    }
+}
+
+/// Where the entry at `path` really is: its parent with symlinks resolved,
+/// followed by its own name.
+fn real_entry_path(fs: &impl FileSystem, path: &Path) -> PathBuf {
+    match (path.parent(), path.components().next_back()) {
+        (Some(parent), Some(Component::Normal(name))) => real_path(fs, parent).join(name),
+        _ => real_path(fs, path),
+    }
+}
+
+/// `path` with `..` and symlinks resolved as far as it exists, and `..`
+/// resolved by name below that.
+fn real_path(fs: &impl FileSystem, path: &Path) -> PathBuf {
+    let mut missing = Vec::new();
+    let mut existing = path;
+
+    let mut real = loop {
+        if let Ok(real) = fs.canonicalize(existing) {
+            break real;
+        }
+        match (existing.parent(), existing.components().next_back()) {
+            (Some(parent), Some(component)) => {
+                missing.push(component);
+                existing = parent;
+            }
+            _ => break existing.to_path_buf(),
+        }
+    };
+
+    for component in missing.into_iter().rev() {
+        match component {
+            Component::ParentDir => {
+                real.pop();
+            }
+            Component::Normal(name) => real.push(name),
+            _ => {}
+        }
+    }
+
+    real
+}
+
+/// Normalizes the last component of every new name to NFC. Parent folders
+/// keep their names as they are on disk.
+pub fn normalize_new_names(plan: &mut [PlannedRename]) {
+    for rename in plan.iter_mut().filter(|rename| rename.skip.is_none()) {
+        let normalized: String = new_file_name(rename).nfc().collect();
+        set_new_file_name(rename, normalized);
+    }
+}
+
+/// A new name that `validate_new_names` replaced with a sanitized one.
+pub struct Sanitized {
+    pub row: usize,
+    pub from: String,
+    pub to: String,
+}
+
+/// Checks the last component of every new name against `profile`. Invalid
+/// names are replaced by a sanitized version if `sanitize` is set, and skip
+/// their row otherwise. `.`, `..` and empty names always skip their row, as
+/// do invalid names of the folders inside `directory` that a row would create
+/// to move its entry into.
+pub fn validate_new_names(
+    fs: &impl FileSystem,
+    directory: &Path,
+    plan: &mut [PlannedRename],
+    profile: Profile,
+    sanitize: bool,
+) -> Vec<Sanitized> {
+    let mut sanitized = Vec::new();
+
+    for rename in plan.iter_mut().filter(|rename| rename.skip.is_none()) {
+        // The folders a move creates are checked but never sanitized.
+        if let Some(problem) = created_folders(fs, directory, rename)
+            .iter()
+            .find_map(|name| validate::check_name(name, profile))
+        {
+            rename.skip = Some(SkipReason::InvalidName(profile, problem));
+            continue;
+        }
+
+        let file_name = new_file_name(rename);
+        let Some(problem) = validate::check_name(file_name, profile) else {
+            continue;
+        };
+
+        // `.`, `..` and empty names are not a component of the new path that
+        // could be replaced.
+        let replaceable = rename.new_path.file_name() == Some(OsStr::new(file_name));
+        if !sanitize || !replaceable {
+            rename.skip = Some(SkipReason::InvalidName(profile, problem));
+            continue;
+        }
+
+        let from = rename.new_name.clone();
+        set_new_file_name(rename, validate::sanitize(file_name, profile));
+        sanitized.push(Sanitized {
+            row: rename.row,
+            from,
+            to: rename.new_name.clone(),
+        });
+    }
+
+    sanitized
+}
+
+/// The names of the missing folders inside `directory` that a rename moves
+/// its entry into, outermost first. Where the move leads outside, as
+/// `--allow-move-outside` permits, the folders are not the tool's to name.
+fn created_folders(fs: &impl FileSystem, directory: &Path, rename: &PlannedRename) -> Vec<String> {
+    let Some(parent) = rename.new_path.parent() else {
+        return Vec::new();
+    };
+    let mut resolved = PathBuf::new();
+    for component in parent.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+    let Ok(inside) = resolved.strip_prefix(directory) else {
+        return Vec::new();
+    };
+
+    let mut folder = directory.to_path_buf();
+    inside
+        .components()
+        .filter_map(|component| {
+            folder.push(component);
+            (!fs.exists(&folder)).then(|| component.as_os_str().to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+fn parent_name(name: &str) -> Option<&str> {
+    name.rsplit_once('/').map(|(parent, _)| parent)
+}
+
+fn new_file_name(rename: &PlannedRename) -> &str {
+    rename
+        .new_name
+        .rsplit_once('/')
+        .map_or(rename.new_name.as_str(), |(_, file_name)| file_name)
+}
+
+/// Replaces the last component of a rename's new name and path.
+fn set_new_file_name(rename: &mut PlannedRename, file_name: String) {
+    if file_name == new_file_name(rename) {
+        return;
+    }
+
+    rename.new_path = rename.new_path.with_file_name(&file_name);
+    rename.new_name = match rename.new_name.rsplit_once('/') {
+        Some((parent, _)) => format!("{parent}/{file_name}"),
+        None => file_name,
+    };
+}
+
+/// The folders inside `directory` that `renames` took entries out of, with
+/// their parents up to `directory`, where they are once all of `renames`
+/// ran, nested folders first. Whichever of them are empty after the run were
+/// left empty by it. `renames` are those that ran, in the order they ran.
+///
+/// A folder that a rename put in place, or that holds an entry one put in
+/// place, is never listed: it may be empty, but the run did not empty it.
+pub fn vacated_folders<'a>(
+    directory: &Path,
+    renames: impl IntoIterator<Item = &'a PlannedRename>,
+) -> Vec<PathBuf> {
+    let mut folders: Vec<PathBuf> = Vec::new();
+    let mut targets: Vec<PathBuf> = Vec::new();
+
+    for rename in renames.into_iter().filter(|rename| rename.skip.is_none()) {
+        // Folders vacated earlier, and entries put in place earlier, move
+        // along with whatever folder they are in.
+        for path in folders.iter_mut().chain(targets.iter_mut()) {
+            if let Ok(rest) = path.strip_prefix(&rename.old_path) {
+                *path = if rest.as_os_str().is_empty() {
+                    rename.new_path.clone()
+                } else {
+                    rename.new_path.join(rest)
+                };
+            }
+        }
+
+        if rename.old_path.parent() != rename.new_path.parent() {
+            folders.extend(
+                rename
+                    .old_path
+                    .ancestors()
+                    .skip(1)
+                    .filter(|folder| folder.starts_with(directory) && *folder != directory)
+                    .map(Path::to_path_buf),
+            );
+        }
+        targets.push(rename.new_path.clone());
+    }
+
+    let mut folders: Vec<PathBuf> = folders
+        .into_iter()
+        .filter(|folder| !targets.iter().any(|target| target.starts_with(folder)))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    folders.sort_by(|a, b| {
+        b.components()
+            .count()
+            .cmp(&a.components().count())
+            .then_with(|| a.cmp(b))
+    });
+    folders
+}
+
+/// Builds a plan for `directory` from listed entries whose `new_name` was
+/// filled in, numbered by the given rows. Entries with an empty `new_name` are
+/// skipped.
+pub fn from_listed(
+    directory: &Path,
+    renames: impl IntoIterator<Item = (usize, ListedEntry)>,
+) -> Vec<PlannedRename> {
+    renames
+        .into_iter()
+        .map(|(row, entry)| {
+            let new_path = target_path(directory, &entry.path, &entry.name, &entry.new_name);
+            let mut rename = PlannedRename::new(
+                row,
+                entry.name.clone(),
+                entry.new_name.clone(),
+                entry.path.clone(),
+                new_path,
+            );
+            rename.expected_kind = Some(entry.kind);
+            if entry.new_name.is_empty() {
+                rename.skip = Some(SkipReason::EmptyName);
+            }
+            rename
+        })
+        .collect()
+}
+
+/// Where `new_name` puts the entry at `old_path`, which the plan calls
+/// `old_name`. As long as the parent stays the same, the new path reuses the
+/// parent of `old_path` exactly as it is on disk, so parents whose names are
+/// not valid UTF-8 still match.
+pub fn target_path(directory: &Path, old_path: &Path, old_name: &str, new_name: &str) -> PathBuf {
+    let file_name = new_name.rsplit('/').next().unwrap_or(new_name);
+
+    match old_path.parent() {
+        Some(parent) if parent_name(new_name) == parent_name(old_name) && !file_name.is_empty() => {
+            parent.join(file_name)
+        }
+        _ => directory.join(new_name),
+    }
+}
+
+/// Checks the plan as a single mapping that is applied all at once, so the
+/// order of the rows does not matter. Only sources of a kind `type_filter`
+/// accepts are renamed, as `CheckOptions::type_filter` describes.
+///
+/// A target may be an existing folder as long as another row moves that
+/// folder away, which is what makes swaps (`a -> b`, `b -> a`) and rotations
+/// possible. A row may move its entry to another folder, which must exist,
+/// be the target of another row, or be created as the row allows. Skipping a
+/// row keeps its source in place, so the target check is repeated until no
+/// more rows are skipped.
+pub fn check_simultaneous(
+    fs: &impl FileSystem,
+    plan: &mut [PlannedRename],
+    type_filter: Option<TypeFilter>,
+) {
+    let mut sources = HashMap::new();
+    let mut targets = HashMap::new();
+
+    for rename in plan.iter_mut().filter(|rename| rename.skip.is_none()) {
+        if rename.old_path == rename.new_path {
+            rename.skip = Some(SkipReason::Unchanged);
+        } else if rename.new_path.starts_with(&rename.old_path) {
+            rename.skip = Some(SkipReason::IntoItself);
+        } else if let Some(&row) = sources.get(&rename.old_path) {
+            rename.skip = Some(SkipReason::DuplicateSource(row));
+        } else if let Some(&row) = targets.get(&rename.new_path) {
+            rename.skip = Some(SkipReason::DuplicateTarget(row));
+        } else if let Some(reason) = check_source(fs, rename, type_filter) {
+            rename.skip = Some(reason);
+        } else {
+            sources.insert(rename.old_path.clone(), rename.row);
+            targets.insert(rename.new_path.clone(), rename.row);
+        }
+    }
+
+    check_targets(fs, plan);
+}
+
+/// Skips rows whose target exists and is not moved away, or whose target
+/// folder is missing, until no more rows are skipped.
+fn check_targets(fs: &impl FileSystem, plan: &mut [PlannedRename]) {
+    loop {
+        let (moving, created): (HashSet<PathBuf>, HashSet<PathBuf>) = plan
+            .iter()
+            .filter(|rename| rename.skip.is_none())
+            .map(|rename| (rename.old_path.clone(), rename.new_path.clone()))
+            .unzip();
+
+        let mut changed = false;
+
+        for rename in plan.iter_mut().filter(|rename| rename.skip.is_none()) {
+            let parent = rename.new_path.parent().unwrap_or(Path::new("/"));
+
+            if fs.exists(&rename.new_path)
+                && !moving.contains(&rename.new_path)
+                && !renames_in_place(fs, rename)
+            {
+                rename.skip = Some(SkipReason::TargetExists(rename.new_path.clone()));
+                changed = true;
+            } else if !fs.is_dir(parent) && !created.contains(parent) && !rename.create_parents {
+                rename.skip = Some(SkipReason::MissingParent(parent.to_path_buf()));
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+}
+
+fn check_source(
+    fs: &impl FileSystem,
+    rename: &PlannedRename,
+    type_filter: Option<TypeFilter>,
+) -> Option<SkipReason> {
+    let Some(kind) = fs.kind(&rename.old_path) else {
+        return Some(SkipReason::MissingSource(rename.old_path.clone()));
+    };
+    // A CSV exported with `--type files` names the type of its rows.
+    let type_filter = type_filter
+        .or(rename.expected_kind.map(|_| TypeFilter::All))
+        .unwrap_or_default();
+
+    match rename.expected_kind {
+        Some(expected) if expected != kind => Some(SkipReason::KindChanged(expected, kind)),
+        _ if !type_filter.accepts(kind) => Some(SkipReason::ExcludedKind(kind)),
+        _ => None,
+    }
+}
+
+/// Orders a plan checked by `check_simultaneous` so every rename can run one
+/// after another: skipped rows first, then each rename as soon as nothing it
+/// depends on is pending. Rows that rename or move something inside a folder
+/// go before the folder itself, while their paths still match the CSV,
+/// unless they move it into the folder's new place: those follow the folder
+/// and rename the entry there. A row moving into a folder another row
+/// creates waits for that row, and chains run from their free end. Each
+/// cycle is broken up by moving one of its entries to a temporary name.
+///
+/// Rows that no order satisfies, such as moves into each other's folders,
+/// are skipped before anything is ordered, moves ahead of renames, along
+/// with the rows that relied on them.
+pub fn order_plan(fs: &impl FileSystem, mut plan: Vec<PlannedRename>) -> Vec<PlannedRename> {
+    plan.sort_by_key(|rename| rename.row);
+    let old_paths: Vec<PathBuf> = plan.iter().map(|rename| rename.old_path.clone()).collect();
+
+    loop {
+        follow_renamed_folders(&mut plan);
+        let stuck = stuck_renames(fs, &plan);
+        if stuck.is_empty() {
+            break;
+        }
+
+        for (rename, old_path) in plan.iter_mut().zip(&old_paths) {
+            rename.old_path = old_path.clone();
+        }
+        // Only moves into another folder can be at odds with a rename, so
+        // the renames they block get to run without them.
+        let (moves, renames): (Vec<usize>, Vec<usize>) = stuck
+            .into_iter()
+            .partition(|&index| plan[index].old_path.parent() != plan[index].new_path.parent());
+        for index in if moves.is_empty() { renames } else { moves } {
+            plan[index].skip = Some(SkipReason::CircularMove);
+        }
+        check_targets(fs, &mut plan);
+    }
+
+    let (renames, mut ordered): (Vec<_>, Vec<_>) =
+        plan.into_iter().partition(|rename| rename.skip.is_none());
+    let (renames, stuck) = order(fs, renames);
+    ordered.extend(renames);
+
+    // Only reached if the dry run above missed a cycle.
+    for mut rename in stuck {
+        rename.skip = Some(SkipReason::CircularMove);
+        ordered.push(rename);
+    }
+
+    ordered
+}
+
+/// Points each row that moves an entry out of a renamed folder into that
+/// folder's new place at where the entry is once the folder was renamed,
+/// as with `a -> A` and `a/sub -> A/SUB`. Only the innermost renamed folder
+/// around the entry counts.
+fn follow_renamed_folders(plan: &mut [PlannedRename]) {
+    let renamed: HashMap<&Path, &Path> = plan
+        .iter()
+        .filter(|rename| rename.skip.is_none())
+        .map(|rename| (rename.old_path.as_path(), rename.new_path.as_path()))
+        .collect();
+
+    let followed: Vec<(usize, PathBuf)> = plan
+        .iter()
+        .enumerate()
+        .filter(|(_, rename)| rename.skip.is_none())
+        .filter_map(|(index, rename)| {
+            let (folder, new_folder) = rename
+                .old_path
+                .ancestors()
+                .skip(1)
+                .find_map(|folder| Some((folder, *renamed.get(folder)?)))?;
+            let rest = rename.old_path.strip_prefix(folder).ok()?;
+            rename
+                .new_path
+                .starts_with(new_folder)
+                .then(|| (index, new_folder.join(rest)))
+        })
+        .collect();
+
+    for (index, old_path) in followed {
+        plan[index].old_path = old_path;
+    }
+}
+
+/// The indexes of the rows `order` would get stuck on, found by ordering
+/// copies of the rows that are not skipped.
+fn stuck_renames(fs: &impl FileSystem, plan: &[PlannedRename]) -> Vec<usize> {
+    let copies = plan
+        .iter()
+        .enumerate()
+        .filter(|(_, rename)| rename.skip.is_none())
+        .map(|(index, rename)| {
+            PlannedRename::new(
+                index,
+                rename.old_name.clone(),
+                rename.new_name.clone(),
+                rename.old_path.clone(),
+                rename.new_path.clone(),
+            )
+        })
+        .collect();
+
+    let (_, stuck) = order(fs, copies);
+    stuck.iter().map(|rename| rename.row).collect()
+}
+
+/// Orders renames that are not skipped, and returns the ones it could not
+/// order, with those of them that were parked under a temporary name taking
+/// it from there.
+fn order(
+    fs: &impl FileSystem,
+    renames: Vec<PlannedRename>,
+) -> (Vec<PlannedRename>, Vec<PlannedRename>) {
+    let mut ordered = Vec::new();
+    let mut taken: HashSet<PathBuf> = renames
+        .iter()
+        .flat_map(|rename| [rename.old_path.clone(), rename.new_path.clone()])
+        .collect();
+
+    let mut pending = Pending::default();
+    for rename in renames {
+        pending.insert(rename);
+    }
+    let mut parked = HashSet::new();
+
+    while !pending.by_source.is_empty() {
+        let mut unblocked: Vec<PathBuf> = pending
+            .by_source
+            .values()
+            .filter(|rename| {
+                !pending.waits(rename) && !pending.by_source.contains_key(&rename.new_path)
+            })
+            .map(|rename| rename.old_path.clone())
+            .collect();
+
+        if !unblocked.is_empty() {
+            unblocked.sort_by_key(|old_path| pending.by_source[old_path].row);
+            for old_path in unblocked {
+                let Some(rename) = pending.remove(&old_path) else {
+                    continue;
+                };
+
+                // Renaming an entry onto another spelling of its own name is
+                // not reliable on every filesystem, so it goes in two steps.
+                if renames_in_place(fs, &rename) {
+                    let (first, second) = split_at_temp(fs, rename, &mut taken);
+                    ordered.push(first);
+                    ordered.push(second);
+                } else {
+                    ordered.push(rename);
+                }
+            }
+            continue;
+        }
+
+        // Everything left waits for its target to be moved away, or for a
+        // rename that does. Park one entry of a cycle under a temporary name,
+        // which unblocks the rest of its cycle.
+        let parkable = pending
+            .by_source
+            .values()
+            .filter(|rename| !pending.waits(rename) && !parked.contains(&rename.old_path))
+            .min_by_key(|rename| rename.row)
+            .map(|rename| rename.old_path.clone());
+
+        let Some(rename) = parkable.and_then(|old_path| pending.remove(&old_path)) else {
+            let mut stuck: Vec<PlannedRename> = pending.by_source.into_values().collect();
+            stuck.sort_by_key(|rename| rename.row);
+            return (ordered, stuck);
+        };
+
+        let (first, second) = split_at_temp(fs, rename, &mut taken);
+        ordered.push(first);
+        parked.insert(second.old_path.clone());
+        pending.insert(second);
+    }
+
+    (ordered, Vec::new())
+}
+
+/// The renames `order_plan` has not ordered yet, by source, with the counts
+/// that tell which of them have to wait.
+#[derive(Default)]
+struct Pending {
+    by_source: HashMap<PathBuf, PlannedRename>,
+    /// How many pending sources and targets lie inside each folder.
+    inside: HashMap<PathBuf, usize>,
+    /// How many pending renames target each path.
+    targets: HashMap<PathBuf, usize>,
+}
+
+impl Pending {
+    fn insert(&mut self, rename: PlannedRename) {
+        for path in [&rename.old_path, &rename.new_path] {
+            for folder in path.ancestors().skip(1) {
+                *self.inside.entry(folder.to_path_buf()).or_default() += 1;
+            }
+        }
+        *self.targets.entry(rename.new_path.clone()).or_default() += 1;
+        self.by_source.insert(rename.old_path.clone(), rename);
+    }
+
+    fn remove(&mut self, old_path: &Path) -> Option<PlannedRename> {
+        let rename = self.by_source.remove(old_path)?;
+
+        for path in [&rename.old_path, &rename.new_path] {
+            for folder in path.ancestors().skip(1) {
+                decrement(&mut self.inside, folder);
+            }
+        }
+        decrement(&mut self.targets, &rename.new_path);
+
+        Some(rename)
+    }
+
+    /// Whether another pending rename has to run first: one that renames or
+    /// moves something inside this rename's source, or one whose target is a
+    /// folder this rename moves into.
+    fn waits(&self, rename: &PlannedRename) -> bool {
+        self.inside.contains_key(&rename.old_path)
+            || rename
+                .new_path
+                .ancestors()
+                .skip(1)
+                .any(|folder| self.targets.contains_key(folder))
+    }
+}
+
+fn decrement(counts: &mut HashMap<PathBuf, usize>, path: &Path) {
+    if let Some(count) = counts.get_mut(path) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(path);
+        }
+    }
+}
+
+/// Splits a rename in two steps through a temporary name.
+fn split_at_temp(
+    fs: &impl FileSystem,
+    rename: PlannedRename,
+    taken: &mut HashSet<PathBuf>,
+) -> (PlannedRename, PlannedRename) {
+    let temp_path = temp_path(fs, &rename.old_path, rename.row, taken);
+    taken.insert(temp_path.clone());
+    let temp_name = match temp_path.file_name() {
+        Some(file_name) => Path::new(&rename.old_name)
+            .with_file_name(file_name)
+            .to_string_lossy()
+            .into_owned(),
+        None => String::new(),
+    };
+
+    let first = PlannedRename::new(
+        rename.row,
+        rename.old_name,
+        temp_name.clone(),
+        rename.old_path,
+        temp_path.clone(),
+    );
+    let mut second = PlannedRename::new(
+        rename.row,
+        temp_name,
+        rename.new_name,
+        temp_path,
+        rename.new_path,
+    );
+    second.create_parents = rename.create_parents;
+
+    (first, second)
+}
+
+/// Picks an unused temporary name next to `old_path`, so the temporary
+/// rename never crosses a filesystem boundary.
+fn temp_path(
+    fs: &impl FileSystem,
+    old_path: &Path,
+    row: usize,
+    taken: &HashSet<PathBuf>,
+) -> PathBuf {
+    let mut candidate = old_path.with_file_name(format!(".rename_tool_tmp_{row}"));
+
+    let mut attempt = 1;
+    while fs.exists(&candidate) || taken.contains(&candidate) {
+        candidate = old_path.with_file_name(format!(".rename_tool_tmp_{row}_{attempt}"));
+        attempt += 1;
+    }
+
+    candidate
+}
@@ -0,0 +1,109 @@
+//! Lossless text form of names that are not valid UTF-8.
+//!
+//! Valid UTF-8 is kept as it is, `%` is written `%25` and every byte that is
+//! not part of valid UTF-8 is written `%XX`, so a Latin-1 `café` becomes
+//! `caf%E9`. Nested names are `/`-separated like everywhere else.
+
+use std::ffi::OsStr;
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+
+/// Header of the CSV column that holds escaped names.
+pub const COLUMN: &str = "old_name_raw";
+
+/// The escaped form of a path, or `None` if it is valid UTF-8 and needs no
+/// escaping.
+pub fn encode(relative: &Path) -> Option<String> {
+    match relative.to_str() {
+        Some(_) => None,
+        None => Some(escape(relative)),
+    }
+}
+
+/// Escapes a path even if it is valid UTF-8.
+pub fn escape(relative: &Path) -> String {
+    let mut escaped = String::new();
+
+    for (index, component) in relative.components().enumerate() {
+        if index > 0 {
+            escaped.push('/');
+        }
+        match component {
+            Component::Normal(name) => escape_component(name, &mut escaped),
+            // The separator that follows stands for the root.
+            Component::RootDir => {}
+            other => escape_component(other.as_os_str(), &mut escaped),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(unix)]
+fn escape_component(name: &OsStr, escaped: &mut String) {
+    use std::os::unix::ffi::OsStrExt;
+
+    for chunk in name.as_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '%' => escaped.push_str("%25"),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            let _ = write!(escaped, "%{byte:02X}");
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn escape_component(name: &OsStr, escaped: &mut String) {
+    escaped.push_str(&name.to_string_lossy().replace('%', "%25"));
+}
+
+/// Reverses `escape`, or `None` if `escaped` has a malformed escape.
+pub fn decode(escaped: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2)?;
+            let hex = std::str::from_utf8(hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    path_from_bytes(bytes)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    Some(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// A relative path as a `/`-separated name, with anything that is not valid
+/// UTF-8 replaced by U+FFFD.
+pub fn lossy_name(relative: &Path) -> String {
+    let mut name = String::new();
+
+    for (index, component) in relative.components().enumerate() {
+        if index > 0 {
+            name.push('/');
+        }
+        name.push_str(&component.as_os_str().to_string_lossy());
+    }
+
+    name
+}
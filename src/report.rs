@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::Error;
+use crate::apply::Outcome;
+use crate::plan::PlannedRename;
+
+/// Machine-readable formats for `--report`.
+#[derive(Clone, Copy)]
+pub enum ReportFormat {
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<ReportFormat, ()> {
+        match value {
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Every step of a run with its outcome, for scripts that wrap the tool.
+///
+/// Rows renamed through a temporary name, and rows that were rolled back,
+/// have more than one entry. The counts use the last entry of each row.
+pub struct Report {
+    source: PathBuf,
+    dry_run: bool,
+    journal: Option<PathBuf>,
+    entries: Vec<ReportEntry>,
+}
+
+#[derive(Default)]
+pub struct Counts {
+    pub rows: usize,
+    pub planned: usize,
+    pub renamed: usize,
+    /// Rows that asked for no rename.
+    pub unchanged: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub rolled_back: usize,
+}
+
+struct ReportEntry {
+    row: usize,
+    old_path: PathBuf,
+    new_path: PathBuf,
+    outcome: &'static str,
+    reason: Option<&'static str>,
+    message: Option<String>,
+}
+
+impl Report {
+    pub fn new(source: &Path, dry_run: bool) -> Report {
+        Report {
+            source: source.to_path_buf(),
+            dry_run,
+            journal: None,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn set_journal(&mut self, journal_path: &Path) {
+        self.journal = Some(journal_path.to_path_buf());
+    }
+
+    /// Records a plan entry as it stands before anything is renamed.
+    pub fn record_planned(&mut self, rename: &PlannedRename) {
+        let (outcome, reason, message) = match &rename.skip {
+            None => ("planned", None, None),
+            Some(reason) => (
+                skipped(rename),
+                Some(reason.code()),
+                Some(reason.to_string()),
+            ),
+        };
+
+        self.push(rename, outcome, reason, message);
+    }
+
+    pub fn record(&mut self, rename: &PlannedRename, outcome: &Outcome) {
+        match outcome {
+            Outcome::Renamed => self.push(rename, "renamed", None, None),
+            Outcome::Skipped { code, message } => {
+                self.push(rename, skipped(rename), Some(code), Some(message.clone()))
+            }
+            Outcome::Failed(message) => {
+                self.push(rename, "failed", Some("io_error"), Some(message.clone()))
+            }
+            Outcome::RolledBack => self.push(rename, "rolled_back", None, None),
+            Outcome::RollbackFailed(message) => self.push(
+                rename,
+                "failed",
+                Some("rollback_failed"),
+                Some(message.clone()),
+            ),
+        }
+    }
+
+    fn push(
+        &mut self,
+        rename: &PlannedRename,
+        outcome: &'static str,
+        reason: Option<&'static str>,
+        message: Option<String>,
+    ) {
+        self.entries.push(ReportEntry {
+            row: rename.row,
+            old_path: rename.old_path.clone(),
+            new_path: rename.new_path.clone(),
+            outcome,
+            reason,
+            message,
+        });
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Json => self.to_json(),
+        }
+    }
+
+    /// Writes the report to `report_path`.
+    pub fn write(&self, format: ReportFormat, report_path: &Path) -> Result<(), Error> {
+        fs::write(report_path, self.render(format) + "\n").map_err(|source| Error::WriteReport {
+            path: report_path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// How many rows ended in each outcome, judged by each row's last entry.
+    pub fn counts(&self) -> Counts {
+        let mut last_outcomes = BTreeMap::new();
+        for entry in &self.entries {
+            last_outcomes.insert(entry.row, entry.outcome);
+        }
+
+        let mut counts = Counts {
+            rows: last_outcomes.len(),
+            ..Counts::default()
+        };
+
+        for outcome in last_outcomes.values() {
+            match *outcome {
+                "planned" => counts.planned += 1,
+                "renamed" => counts.renamed += 1,
+                "unchanged" => counts.unchanged += 1,
+                "skipped" => counts.skipped += 1,
+                "failed" => counts.failed += 1,
+                _ => counts.rolled_back += 1,
+            }
+        }
+
+        counts
+    }
+
+    fn to_json(&self) -> String {
+        let counts = self.counts();
+        let mut json = String::from("{\n");
+        let _ = writeln!(json, "  \"source\": {},", json_path(&self.source));
+        let _ = writeln!(json, "  \"dry_run\": {},", self.dry_run);
+        let _ = writeln!(
+            json,
+            "  \"journal\": {},",
+            self.journal
+                .as_deref()
+                .map_or("null".to_string(), json_path)
+        );
+
+        json.push_str("  \"entries\": [");
+        for (index, entry) in self.entries.iter().enumerate() {
+            let separator = if index == 0 { "\n" } else { ",\n" };
+            let _ = write!(
+                json,
+                "{separator}    {{\"row\": {}, \"old_path\": {}, \"new_path\": {}, \"outcome\": \"{}\", \"reason\": {}, \"message\": {}}}",
+                entry.row,
+                json_path(&entry.old_path),
+                json_path(&entry.new_path),
+                entry.outcome,
+                entry.reason.map_or("null".to_string(), json_string),
+                entry
+                    .message
+                    .as_deref()
+                    .map_or("null".to_string(), json_string),
+            );
+        }
+        json.push_str(if self.entries.is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+
+        let _ = write!(
+            json,
+            "  \"counts\": {{\"rows\": {}, \"planned\": {}, \"renamed\": {}, \"unchanged\": {}, \"skipped\": {}, \"failed\": {}, \"rolled_back\": {}",
+            counts.rows,
+            counts.planned,
+            counts.renamed,
+            counts.unchanged,
+            counts.skipped,
+            counts.failed,
+            counts.rolled_back
+        );
+        json.push_str("}\n}");
+
+        json
+    }
+}
+
+/// The outcome of a skipped row: `unchanged` if it asked for no rename.
+fn skipped(rename: &PlannedRename) -> &'static str {
+    if rename.is_requested() {
+        "skipped"
+    } else {
+        "unchanged"
+    }
+}
+
+fn json_path(path: &Path) -> String {
+    json_string(&path.to_string_lossy())
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
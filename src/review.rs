@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Error;
+use crate::entry::{Context, EntryKind};
+use crate::filesystem::FileSystem;
+use crate::journal;
+use crate::plan::{self, PlannedRename};
+use crate::rawname;
+
+const PROMPT: &str = "Rename? [y]es, [n]o, [e]dit, [a]ll remaining, [q]uit: ";
+
+/// Runs `recheck` over the plan as read, then asks about every row that
+/// passed its checks, in row order, reading the answers from `input` and
+/// writing the questions to `output`.
+///
+/// Declined rows, and the rows left when the user quits, are removed from
+/// the plan; the number removed is returned. After a new name is edited, the
+/// declined rows are dropped, the skips the checks gave are cleared, and
+/// `recheck` runs again, so rows an edit freed up are asked about too. The
+/// edited row is asked about again, with the reason if it now fails its
+/// checks. The end of `input` counts as quitting.
+pub fn review(
+    fs: &impl FileSystem,
+    directory: &Path,
+    plan: &mut Vec<PlannedRename>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    mut recheck: impl FnMut(&mut [PlannedRename]),
+) -> io::Result<usize> {
+    // Rows skipped while reading, such as those without a new name, stay
+    // skipped whatever the checks say.
+    let read_skipped: HashSet<usize> = plan
+        .iter()
+        .filter(|rename| rename.skip.is_some())
+        .map(|rename| rename.row)
+        .collect();
+    recheck(plan);
+
+    let mut asked = HashSet::new();
+    let mut declined = HashSet::new();
+    let mut accept_all = false;
+    let mut quit = false;
+
+    while let Some(row) = plan
+        .iter()
+        .find(|rename| rename.skip.is_none() && !asked.contains(&rename.row))
+        .map(|rename| rename.row)
+    {
+        asked.insert(row);
+        if quit {
+            declined.insert(row);
+            continue;
+        }
+
+        while !accept_all {
+            let Some(rename) = plan.iter().find(|rename| rename.row == row) else {
+                break;
+            };
+
+            writeln!(
+                output,
+                "Row {row}: {} -> {}",
+                rename.old_name, rename.new_name
+            )?;
+            match Context::of(fs, &rename.old_path) {
+                Some(context) => writeln!(output, "  {context}")?,
+                None => writeln!(output, "  (no longer exists)")?,
+            }
+            if let Some(reason) = &rename.skip {
+                writeln!(output, "  will be skipped: {reason}")?;
+            }
+            write!(output, "{PROMPT}")?;
+            output.flush()?;
+
+            match read_answer(input)?.as_deref() {
+                Some("y" | "yes") => break,
+                Some("n" | "no") => {
+                    declined.insert(row);
+                    break;
+                }
+                Some("a" | "all") => accept_all = true,
+                Some("q" | "quit") | None => {
+                    declined.insert(row);
+                    quit = true;
+                    break;
+                }
+                Some("e" | "edit") => {
+                    write!(output, "New name for {}: ", rename.old_name)?;
+                    output.flush()?;
+
+                    match read_answer(input)? {
+                        Some(new_name) if !new_name.is_empty() => {
+                            set_new_name(directory, plan, row, new_name);
+                            plan.retain(|rename| !declined.contains(&rename.row));
+                            for rename in plan.iter_mut() {
+                                if !read_skipped.contains(&rename.row) {
+                                    rename.skip = None;
+                                }
+                            }
+                            recheck(plan);
+                        }
+                        Some(_) => {}
+                        None => {
+                            declined.insert(row);
+                            quit = true;
+                            break;
+                        }
+                    }
+                }
+                Some(_) => writeln!(output, "Please answer y, n, e, a or q.")?,
+            }
+        }
+    }
+
+    plan.retain(|rename| !declined.contains(&rename.row));
+    Ok(declined.len())
+}
+
+/// The next line of `input`, trimmed, or `None` at its end.
+fn read_answer(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+fn set_new_name(directory: &Path, plan: &mut [PlannedRename], row: usize, new_name: String) {
+    if let Some(rename) = plan.iter_mut().find(|rename| rename.row == row) {
+        rename.new_path =
+            plan::target_path(directory, &rename.old_path, &rename.old_name, &new_name);
+        rename.new_name = new_name;
+    }
+}
+
+/// Default location for the decisions of a review: a timestamped file in
+/// the current directory.
+pub fn default_decisions_path() -> PathBuf {
+    journal::timestamped_path("rename_decisions")
+}
+
+/// A row a review accepted, as it stood before the plan was ordered.
+pub struct Decision {
+    row: usize,
+    old_name: String,
+    new_name: String,
+    kind: &'static str,
+    raw_name: Option<String>,
+}
+
+/// The rows of a reviewed plan that passed their checks, with their final
+/// names. Take them before the plan is ordered, which may split a row
+/// through a temporary name or move its old path.
+pub fn decisions(fs: &impl FileSystem, directory: &Path, plan: &[PlannedRename]) -> Vec<Decision> {
+    plan.iter()
+        .filter(|rename| rename.skip.is_none())
+        .map(|rename| Decision {
+            row: rename.row,
+            old_name: rename.old_name.clone(),
+            new_name: rename.new_name.clone(),
+            kind: rename
+                .expected_kind
+                .or_else(|| fs.kind(&rename.old_path))
+                .map_or("", EntryKind::as_str),
+            raw_name: rename
+                .old_path
+                .strip_prefix(directory)
+                .ok()
+                .and_then(rawname::encode),
+        })
+        .collect()
+}
+
+/// Writes `decisions` as a CSV that `import` can replay, leaving out the
+/// rows the final checks of `plan` skipped or dropped.
+pub fn write_decisions(
+    decisions: &[Decision],
+    plan: &[PlannedRename],
+    csv_path: &Path,
+) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_path(csv_path).map_err(|source| Error::CreateCsv {
+        path: csv_path.to_path_buf(),
+        source,
+    })?;
+
+    let write_error = |source| Error::WriteCsv {
+        path: csv_path.to_path_buf(),
+        source,
+    };
+
+    let planned: HashSet<usize> = plan.iter().map(|rename| rename.row).collect();
+    let skipped: HashSet<usize> = plan
+        .iter()
+        .filter(|rename| rename.skip.is_some())
+        .map(|rename| rename.row)
+        .collect();
+    let accepted: Vec<&Decision> = decisions
+        .iter()
+        .filter(|decision| planned.contains(&decision.row) && !skipped.contains(&decision.row))
+        .collect();
+
+    // The raw column is only written when a name needs it.
+    let mut headers = vec!["old_name", "new_name", "type"];
+    if accepted.iter().any(|decision| decision.raw_name.is_some()) {
+        headers.push(rawname::COLUMN);
+    }
+    writer.write_record(&headers).map_err(write_error)?;
+
+    for decision in accepted {
+        let mut record = vec![
+            decision.old_name.as_str(),
+            decision.new_name.as_str(),
+            decision.kind,
+        ];
+        if headers.len() > 3 {
+            record.push(decision.raw_name.as_deref().unwrap_or(""));
+        }
+        writer.write_record(record).map_err(write_error)?;
+    }
+
+    writer
+        .flush()
+        .map_err(|error| write_error(csv::Error::from(error)))
+}
@@ -0,0 +1,303 @@
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use calamine::{Data, Reader};
+use rust_xlsxwriter as xlsx;
+use zip::write::SimpleFileOptions;
+
+use crate::columns::Value;
+use crate::datetime::DateTime;
+
+/// The spreadsheet formats plans are written to and read from directly,
+/// instead of as a CSV a spreadsheet would reinterpret.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Xlsx,
+    Ods,
+}
+
+impl Format {
+    /// The format a path's extension names, or `None` for a CSV.
+    pub fn of(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "xlsx" => Some(Format::Xlsx),
+            "ods" => Some(Format::Ods),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `rows` as a single sheet, the first row as bold headers. Columns
+/// without numbers or times are formatted as text, so names typed into them
+/// later are kept as typed too. Numbers and times are stored as such, times
+/// shown as `yyyy-mm-dd hh:mm:ss` in UTC.
+pub fn write(format: Format, rows: &[Vec<Value>]) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Xlsx => write_xlsx(rows).map_err(|error| error.to_string()),
+        Format::Ods => write_ods(rows).map_err(|error| error.to_string()),
+    }
+}
+
+/// Reads the first sheet as text, each row with its number in the sheet.
+/// Empty rows are left out. Whole numbers read without a decimal point and
+/// dates as `yyyy-mm-dd`, with the time if there is one.
+pub fn read(contents: Vec<u8>) -> Result<Vec<(usize, Vec<String>)>, String> {
+    let mut workbook =
+        calamine::open_workbook_auto_from_rs(Cursor::new(contents)).map_err(|e| e.to_string())?;
+    let range = match workbook.worksheet_range_at(0) {
+        Some(range) => range.map_err(|error| error.to_string())?,
+        None => return Ok(Vec::new()),
+    };
+
+    // The range starts at the first cell that is not empty, which need not
+    // be A1.
+    let (first_row, first_column) = range.start().unwrap_or((0, 0));
+    let rows = range
+        .rows()
+        .enumerate()
+        .filter(|(_, cells)| cells.iter().any(|cell| *cell != Data::Empty))
+        .map(|(index, cells)| {
+            let mut row = vec![String::new(); first_column as usize];
+            row.extend(cells.iter().map(cell_text));
+            (first_row as usize + index + 1, row)
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::Float(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+            format!("{}", *number as i64)
+        }
+        Data::DateTime(date) => {
+            // Days since 1899-12-30, as both formats count them.
+            let seconds = ((date.as_f64() - 25_569.0) * 86_400.0).round();
+            if seconds < 0.0 {
+                return cell.to_string();
+            }
+            let time = DateTime::from_system_time(
+                UNIX_EPOCH + std::time::Duration::from_secs(seconds as u64),
+            );
+            if date.as_f64().fract() == 0.0 {
+                time.format("%Y-%m-%d")
+            } else {
+                time.format("%Y-%m-%d %H:%M:%S")
+            }
+        }
+        Data::DateTimeIso(date) => date.replacen('T', " ", 1),
+        _ => cell.to_string(),
+    }
+}
+
+/// Seconds since the Unix epoch, negative before it.
+fn timestamp(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(error) => -(error.duration().as_secs() as i64),
+    }
+}
+
+/// Whether a column holds only text, and is formatted as such.
+fn is_text_column(rows: &[Vec<Value>], column: usize) -> bool {
+    rows.iter()
+        .skip(1)
+        .all(|row| !matches!(row.get(column), Some(Value::Number(_) | Value::Time(_))))
+}
+
+fn write_xlsx(rows: &[Vec<Value>]) -> Result<Vec<u8>, xlsx::XlsxError> {
+    let mut workbook = xlsx::Workbook::new();
+    let sheet = workbook.add_worksheet();
+
+    let text = xlsx::Format::new().set_num_format("@");
+    let header = text.clone().set_bold();
+    let datetime = xlsx::Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    for column in 0..width {
+        if is_text_column(rows, column) {
+            sheet.set_column_format(column as u16, &text)?;
+        }
+    }
+
+    for (row, cells) in rows.iter().enumerate() {
+        let row = row as u32;
+        let format = if row == 0 { &header } else { &text };
+
+        for (column, value) in cells.iter().enumerate() {
+            let column = column as u16;
+            match value {
+                Value::Empty => {}
+                Value::Text(value) => {
+                    sheet.write_string_with_format(row, column, value, format)?;
+                }
+                Value::Number(number) => {
+                    sheet.write_number(row, column, *number as f64)?;
+                }
+                Value::Time(time) => match xlsx::ExcelDateTime::from_timestamp(timestamp(*time)) {
+                    Ok(date) => {
+                        sheet.write_datetime_with_format(row, column, &date, &datetime)?;
+                    }
+                    // Excel has no dates before 1900.
+                    Err(_) => {
+                        sheet.write_string(row, column, value.to_string())?;
+                    }
+                },
+            }
+        }
+    }
+
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.autofit();
+    workbook.save_to_buffer()
+}
+
+const ODS_MIMETYPE: &str = "application/vnd.oasis.opendocument.spreadsheet";
+
+const ODS_MANIFEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+
+/// The start of `content.xml`, up to the table's columns: a text style, a
+/// bold one for headers and a date and time one.
+const ODS_CONTENT_START: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:style="urn:oasis:names:tc:opendocument:xmlns:style:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:number="urn:oasis:names:tc:opendocument:xmlns:datastyle:1.0" xmlns:fo="urn:oasis:names:tc:opendocument:xmlns:xsl-fo-compatible:1.0" office:version="1.2">
+<office:automatic-styles>
+<number:text-style style:name="N1"><number:text-content/></number:text-style>
+<number:date-style style:name="N2"><number:year number:style="long"/><number:text>-</number:text><number:month number:style="long"/><number:text>-</number:text><number:day number:style="long"/><number:text> </number:text><number:hours number:style="long"/><number:text>:</number:text><number:minutes number:style="long"/><number:text>:</number:text><number:seconds number:style="long"/></number:date-style>
+<style:style style:name="text" style:family="table-cell" style:data-style-name="N1"/>
+<style:style style:name="header" style:family="table-cell" style:data-style-name="N1"><style:text-properties fo:font-weight="bold"/></style:style>
+<style:style style:name="time" style:family="table-cell" style:data-style-name="N2"/>
+</office:automatic-styles>
+<office:body>
+<office:spreadsheet>
+<table:table table:name="Sheet1">
+"#;
+
+const ODS_CONTENT_END: &str =
+    "</table:table>\n</office:spreadsheet>\n</office:body>\n</office:document-content>\n";
+
+fn write_ods(rows: &[Vec<Value>]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut content = String::from(ODS_CONTENT_START);
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    for column in 0..width {
+        content.push_str(if is_text_column(rows, column) {
+            "<table:table-column table:default-cell-style-name=\"text\"/>\n"
+        } else {
+            "<table:table-column/>\n"
+        });
+    }
+
+    for (index, cells) in rows.iter().enumerate() {
+        if index == 0 {
+            content.push_str("<table:table-header-rows>");
+        }
+        content.push_str("<table:table-row>");
+
+        for value in cells {
+            let cell = match value {
+                Value::Empty => "<table:table-cell/>".to_string(),
+                Value::Text(text) => format!(
+                    "<table:table-cell table:style-name=\"{}\" office:value-type=\"string\" office:string-value=\"{}\"><text:p>{}</text:p></table:table-cell>",
+                    if index == 0 { "header" } else { "text" },
+                    xml_attribute(text),
+                    ods_text(text)
+                ),
+                Value::Number(number) => format!(
+                    "<table:table-cell office:value-type=\"float\" office:value=\"{number}\"><text:p>{number}</text:p></table:table-cell>"
+                ),
+                Value::Time(time) => {
+                    let time = DateTime::from_system_time(*time);
+                    format!(
+                        "<table:table-cell table:style-name=\"time\" office:value-type=\"date\" office:date-value=\"{}\"><text:p>{}</text:p></table:table-cell>",
+                        time.format("%Y-%m-%dT%H:%M:%S"),
+                        time.format("%Y-%m-%d %H:%M:%S")
+                    )
+                }
+            };
+            content.push_str(&cell);
+        }
+
+        content.push_str("</table:table-row>\n");
+        if index == 0 {
+            content.push_str("</table:table-header-rows>\n");
+        }
+    }
+    content.push_str(ODS_CONTENT_END);
+
+    // The mimetype comes first and uncompressed, so it can be read at a
+    // fixed offset.
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let stored = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default();
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(ODS_MIMETYPE.as_bytes())?;
+    zip.start_file("META-INF/manifest.xml", deflated)?;
+    zip.write_all(ODS_MANIFEST.as_bytes())?;
+    zip.start_file("content.xml", deflated)?;
+    zip.write_all(content.as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Escapes text for an attribute, where whitespace other than a space would
+/// otherwise become a space.
+fn xml_attribute(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push_str(&format!("&#{};", u32::from(c))),
+            c => escaped.push(xml_char(c)),
+        }
+    }
+
+    escaped
+}
+
+/// Control characters other than whitespace are not allowed in XML at all.
+fn xml_char(c: char) -> char {
+    if c.is_control() {
+        char::REPLACEMENT_CHARACTER
+    } else {
+        c
+    }
+}
+
+/// Escapes text for a `<text:p>`, where runs of spaces would otherwise
+/// collapse into one and tabs and line breaks would become spaces.
+/// Spreadsheets show this text; `office:string-value` holds it exactly.
+fn ods_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut after_space = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            // Only a single space between other characters is kept as is.
+            ' ' if after_space || matches!(chars.peek(), None | Some(' ')) => {
+                escaped.push_str("<text:s/>");
+            }
+            '\t' => escaped.push_str("<text:tab/>"),
+            '\n' => escaped.push_str("<text:line-break/>"),
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '\r' => escaped.push_str("&#13;"),
+            c => escaped.push(xml_char(c)),
+        }
+        after_space = c == ' ';
+    }
+
+    escaped
+}
@@ -0,0 +1,92 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::Error;
+use crate::export::ListedEntry;
+
+/// How `rename_matching` applies a pattern.
+#[derive(Default)]
+pub struct RegexOptions {
+    pub ignore_case: bool,
+    pub replace_all: bool,
+    /// Only entries whose relative path matches this pattern are considered.
+    pub filter: Option<String>,
+}
+
+/// Keeps every listed entry whose name matches `pattern`, with `new_name`
+/// set to the name with the match replaced by `replacement` (which may use
+/// `$1` or `${name}` capture groups).
+///
+/// Only the last component of a nested name is rewritten, and entries the
+/// replacement leaves unchanged are dropped.
+pub fn rename_matching(
+    mut listed: Vec<ListedEntry>,
+    pattern: &str,
+    replacement: &str,
+    options: &RegexOptions,
+) -> Result<Vec<ListedEntry>, Error> {
+    let regex = build_regex(pattern, options.ignore_case)?;
+    let filter = options
+        .filter
+        .as_deref()
+        .map(|filter| build_regex(filter, options.ignore_case))
+        .transpose()?;
+
+    listed.retain_mut(|entry| {
+        if filter
+            .as_ref()
+            .is_some_and(|filter| !filter.is_match(&entry.name))
+        {
+            return false;
+        }
+
+        match replace_file_name(&regex, &entry.name, replacement, options.replace_all) {
+            Some(new_name) => {
+                entry.new_name = new_name;
+                true
+            }
+            None => false,
+        }
+    });
+
+    Ok(listed)
+}
+
+/// `name` with the first match of `regex` in its last component, or every
+/// match if `replace_all` is set, replaced by `replacement`. `None` if that
+/// leaves the name unchanged.
+pub(crate) fn replace_file_name(
+    regex: &Regex,
+    name: &str,
+    replacement: &str,
+    replace_all: bool,
+) -> Option<String> {
+    let (parent, file_name) = match name.rsplit_once('/') {
+        Some((parent, file_name)) => (Some(parent), file_name),
+        None => (None, name),
+    };
+
+    let replaced = if replace_all {
+        regex.replace_all(file_name, replacement)
+    } else {
+        regex.replace(file_name, replacement)
+    };
+
+    if replaced == file_name {
+        return None;
+    }
+
+    Some(match parent {
+        Some(parent) => format!("{parent}/{replaced}"),
+        None => replaced.into_owned(),
+    })
+}
+
+pub(crate) fn build_regex(pattern: &str, ignore_case: bool) -> Result<Regex, Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|source| Error::InvalidPattern {
+            pattern: pattern.to_string(),
+            source,
+        })
+}
@@ -0,0 +1,229 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use crate::Error;
+use crate::datetime::DateTime;
+use crate::filesystem::{FileSystem, Metadata};
+
+/// A naming template for `export --template`, e.g.
+/// `{counter:3}_{parent|lower}_{name}`.
+///
+/// Placeholders are written `{field}`, `{field:argument}` and may be followed
+/// by case transforms, `{name|upper|title}`. `{{` and `}}` are literal braces.
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+enum Part {
+    Literal(String),
+    Placeholder(Field, Vec<Transform>),
+}
+
+enum Field {
+    Name,
+    Stem,
+    Extension,
+    Parent,
+    Counter { width: usize },
+    Modified { format: String },
+    Created { format: String },
+}
+
+enum Transform {
+    Upper,
+    Lower,
+    Title,
+}
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(template: &str) -> Result<Template, Error> {
+        parse_template(template).map_err(|message| Error::InvalidTemplate {
+            template: template.to_string(),
+            message,
+        })
+    }
+}
+
+fn parse_template(template: &str) -> Result<Template, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => placeholder.push(c),
+                        None => return Err(format!("unclosed placeholder {{{placeholder}")),
+                    }
+                }
+
+                if !literal.is_empty() {
+                    parts.push(Part::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(parse_placeholder(&placeholder)?);
+            }
+            '}' => return Err("unmatched }, write }} for a literal brace".to_string()),
+            _ => literal.push(c),
+        }
+    }
+
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+
+    Ok(Template { parts })
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part, String> {
+    let mut pieces = placeholder.split('|');
+    let head = pieces.next().unwrap_or("");
+    let (name, argument) = match head.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument)),
+        None => (head.trim(), None),
+    };
+
+    let field = match (name, argument) {
+        ("name", None) => Field::Name,
+        ("stem", None) => Field::Stem,
+        ("ext", None) => Field::Extension,
+        ("parent", None) => Field::Parent,
+        ("counter", None) => Field::Counter { width: 1 },
+        ("counter", Some(width)) => match width.trim().parse() {
+            Ok(width) => Field::Counter { width },
+            Err(_) => return Err(format!("invalid counter width {width:?}")),
+        },
+        ("modified", format) => Field::Modified {
+            format: format.unwrap_or(DEFAULT_DATE_FORMAT).to_string(),
+        },
+        ("created", format) => Field::Created {
+            format: format.unwrap_or(DEFAULT_DATE_FORMAT).to_string(),
+        },
+        (name, Some(_)) if ["name", "stem", "ext", "parent"].contains(&name) => {
+            return Err(format!("{{{name}}} does not take an argument"));
+        }
+        (name, _) => return Err(format!("unknown placeholder {{{name}}}")),
+    };
+
+    let transforms = pieces
+        .map(|transform| match transform.trim() {
+            "upper" => Ok(Transform::Upper),
+            "lower" => Ok(Transform::Lower),
+            "title" => Ok(Transform::Title),
+            other => Err(format!("unknown transform |{other}")),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Part::Placeholder(field, transforms))
+}
+
+impl Template {
+    /// Renders the new name for the entry at `path` in `fs`. `counter` is the
+    /// entry's 1-based position in the export and `parent` the name of the
+    /// folder it is in. Dates are those of the entry itself, not of what a
+    /// symlink points to.
+    pub fn render(
+        &self,
+        fs: &impl FileSystem,
+        path: &Path,
+        counter: usize,
+        parent: &str,
+    ) -> Result<String, String> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, extension),
+            _ => (name.as_str(), ""),
+        };
+
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            let (field, transforms) = match part {
+                Part::Literal(literal) => {
+                    rendered.push_str(literal);
+                    continue;
+                }
+                Part::Placeholder(field, transforms) => (field, transforms),
+            };
+
+            let mut value = match field {
+                Field::Name => name.clone(),
+                Field::Stem => stem.to_string(),
+                Field::Extension => extension.to_string(),
+                Field::Parent => parent.to_string(),
+                Field::Counter { width } => format!("{counter:0width$}"),
+                Field::Modified { format } => {
+                    entry_time(fs, path, "modification", |metadata| metadata.modified)?
+                        .format(format)
+                }
+                Field::Created { format } => {
+                    entry_time(fs, path, "creation", |metadata| metadata.created)?.format(format)
+                }
+            };
+
+            for transform in transforms {
+                value = match transform {
+                    Transform::Upper => value.to_uppercase(),
+                    Transform::Lower => value.to_lowercase(),
+                    Transform::Title => title_case(&value),
+                };
+            }
+
+            rendered.push_str(&value);
+        }
+
+        Ok(rendered)
+    }
+}
+
+/// The time `pick` takes from the metadata of `path`, or why it is
+/// unavailable.
+fn entry_time(
+    fs: &impl FileSystem,
+    path: &Path,
+    what: &str,
+    pick: impl FnOnce(Metadata) -> Option<SystemTime>,
+) -> Result<DateTime, String> {
+    let metadata = fs
+        .metadata(path)
+        .map_err(|error| format!("{what} time unavailable: {error}"))?;
+    let time = pick(metadata).ok_or_else(|| format!("{what} time unavailable"))?;
+    Ok(DateTime::from_system_time(time))
+}
+
+/// Upper-cases the first letter of every word and lower-cases the rest.
+/// Words are separated by spaces, `_`, `-` and `.`.
+fn title_case(value: &str) -> String {
+    let mut titled = String::with_capacity(value.len());
+    let mut at_word_start = true;
+
+    for c in value.chars() {
+        if at_word_start {
+            titled.extend(c.to_uppercase());
+        } else {
+            titled.extend(c.to_lowercase());
+        }
+        at_word_start = matches!(c, ' ' | '_' | '-' | '.');
+    }
+
+    titled
+}
@@ -0,0 +1,630 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::PathBuf;
+
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table, TableState};
+
+use crate::Error;
+use crate::export::ListedEntry;
+use crate::plan::{self, PlannedRename};
+use crate::substitute;
+
+const HELP: &str = "Enter edit  x clear  / filter  r replace  s sort  a apply  q quit";
+
+/// What a listed entry will do if the session is applied, as of the last
+/// check.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Status {
+    /// No new name was given.
+    Unchanged,
+    /// The entry passes its checks and gets this name, which may differ from
+    /// the one typed if the checks normalized or sanitized it.
+    Renamed(String),
+    /// The entry's row would be skipped for this reason.
+    Problem(String),
+}
+
+/// The column the table is sorted by, cycled with `s`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SortKey {
+    Name,
+    NewName,
+    Kind,
+    /// Problems first, then renames, then unchanged entries.
+    Status,
+}
+
+impl SortKey {
+    fn next(self) -> SortKey {
+        match self {
+            SortKey::Name => SortKey::NewName,
+            SortKey::NewName => SortKey::Kind,
+            SortKey::Kind => SortKey::Status,
+            SortKey::Status => SortKey::Name,
+        }
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SortKey::Name => "old name",
+            SortKey::NewName => "new name",
+            SortKey::Kind => "type",
+            SortKey::Status => "status",
+        })
+    }
+}
+
+/// What the caller should do after a key press.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Continue,
+    Quit,
+    Apply,
+}
+
+/// A line of text being typed, with the cursor counted in characters.
+struct Input {
+    text: String,
+    cursor: usize,
+}
+
+impl Input {
+    fn new(text: String) -> Input {
+        Input {
+            cursor: text.chars().count(),
+            text,
+        }
+    }
+
+    fn byte_index(&self, cursor: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(cursor)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+
+    fn before_cursor(&self) -> &str {
+        &self.text[..self.byte_index(self.cursor)]
+    }
+
+    /// Applies an editing key, returning whether it was one.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let length = self.text.chars().count();
+
+        match key.code {
+            KeyCode::Char(c) => {
+                let index = self.byte_index(self.cursor);
+                self.text.insert(index, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let index = self.byte_index(self.cursor);
+                self.text.remove(index);
+            }
+            KeyCode::Delete if self.cursor < length => {
+                let index = self.byte_index(self.cursor);
+                self.text.remove(index);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(length),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = length,
+            KeyCode::Backspace | KeyCode::Delete => {}
+            _ => return false,
+        }
+        true
+    }
+}
+
+enum Mode {
+    Browse,
+    Edit(Input),
+    Filter(Input),
+    Find(Input),
+    Replace { pattern: String, input: Input },
+    Confirm { question: String, action: Action },
+}
+
+/// The state of a `tui` session: the listed entries with the new names
+/// typed so far, how each would fare if applied, and what the table shows.
+///
+/// Entries are numbered from 1 in listing order, which is also the row a
+/// problem refers to. Every change to a new name runs `check` over the plan
+/// built from the entries that have one, the same way `edit` and `regex` do.
+pub struct Session {
+    directory: PathBuf,
+    entries: Vec<ListedEntry>,
+    statuses: Vec<Status>,
+    /// Indexes into `entries`, filtered and sorted as shown.
+    view: Vec<usize>,
+    sort: SortKey,
+    filter: String,
+    table: TableState,
+    /// Rows that fit on the screen, for paging.
+    page: usize,
+    mode: Mode,
+    message: Option<String>,
+}
+
+impl Session {
+    /// Starts a session over entries listed from `directory`, checking any
+    /// new names they already have.
+    pub fn new(
+        directory: PathBuf,
+        entries: Vec<ListedEntry>,
+        check: &mut impl FnMut(&mut [PlannedRename]),
+    ) -> Session {
+        let mut session = Session {
+            directory,
+            statuses: vec![Status::Unchanged; entries.len()],
+            view: (0..entries.len()).collect(),
+            entries,
+            sort: SortKey::Name,
+            filter: String::new(),
+            table: TableState::default(),
+            page: 10,
+            mode: Mode::Browse,
+            message: None,
+        };
+        session
+            .table
+            .select((!session.view.is_empty()).then_some(0));
+        session.recheck(check);
+        session
+    }
+
+    /// The entries shown, in order, with their status.
+    pub fn visible(&self) -> impl Iterator<Item = (&ListedEntry, &Status)> {
+        self.view
+            .iter()
+            .map(|&index| (&self.entries[index], &self.statuses[index]))
+    }
+
+    /// The entry under the cursor.
+    pub fn selected(&self) -> Option<&ListedEntry> {
+        self.selected_index().map(|index| &self.entries[index])
+    }
+
+    pub fn sort(&self) -> SortKey {
+        self.sort
+    }
+
+    /// The last message shown to the user, such as an invalid pattern.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// The entries that were given a new name, numbered by row, ready for
+    /// `plan::from_listed`.
+    pub fn renames(&self) -> Vec<(usize, ListedEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| is_renamed(entry))
+            .map(|(index, entry)| (index + 1, entry.clone()))
+            .collect()
+    }
+
+    fn selected_index(&self) -> Option<usize> {
+        self.table
+            .selected()
+            .and_then(|position| self.view.get(position).copied())
+    }
+
+    /// Checks every entry with a new name again, as one plan.
+    fn recheck(&mut self, check: &mut impl FnMut(&mut [PlannedRename])) {
+        let mut plan = plan::from_listed(&self.directory, self.renames());
+        check(&mut plan);
+
+        self.statuses.fill(Status::Unchanged);
+        for rename in plan {
+            self.statuses[rename.row - 1] = match rename.skip {
+                Some(reason) => Status::Problem(reason.to_string()),
+                None => Status::Renamed(rename.new_name),
+            };
+        }
+    }
+
+    /// Filters and sorts the entries again, keeping the cursor on the same
+    /// entry if it is still shown.
+    fn refresh_view(&mut self) {
+        let selected = self.selected_index();
+        let filter = self.filter.to_lowercase();
+
+        let mut view: Vec<usize> = (0..self.entries.len())
+            .filter(|&index| {
+                let entry = &self.entries[index];
+                filter.is_empty()
+                    || entry.name.to_lowercase().contains(&filter)
+                    || entry.new_name.to_lowercase().contains(&filter)
+            })
+            .collect();
+        view.sort_by(|&a, &b| self.compare(a, b).then_with(|| a.cmp(&b)));
+        self.view = view;
+
+        let position = selected
+            .and_then(|selected| self.view.iter().position(|&index| index == selected))
+            .unwrap_or(0);
+        self.table
+            .select((!self.view.is_empty()).then_some(position));
+    }
+
+    fn compare(&self, a: usize, b: usize) -> Ordering {
+        let (a_entry, b_entry) = (&self.entries[a], &self.entries[b]);
+
+        match self.sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::NewName => shown_name(a_entry).cmp(shown_name(b_entry)),
+            SortKey::Kind => a_entry.kind.as_str().cmp(b_entry.kind.as_str()),
+            SortKey::Status => {
+                let rank = |status: &Status| match status {
+                    Status::Problem(_) => 0,
+                    Status::Renamed(_) => 1,
+                    Status::Unchanged => 2,
+                };
+                rank(&self.statuses[a]).cmp(&rank(&self.statuses[b]))
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, by: isize) {
+        if self.view.is_empty() {
+            return;
+        }
+        let last = self.view.len() - 1;
+        let position = self.table.selected().unwrap_or(0);
+        self.table
+            .select(Some(position.saturating_add_signed(by).min(last)));
+    }
+
+    /// Reacts to a key press. Changes to new names are checked right away
+    /// with `check`.
+    pub fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        check: &mut impl FnMut(&mut [PlannedRename]),
+    ) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => return self.browse(key, check),
+            Mode::Edit(mut input) => match key.code {
+                KeyCode::Enter => {
+                    if let Some(index) = self.selected_index() {
+                        let entry = &mut self.entries[index];
+                        entry.new_name = if input.text == entry.name {
+                            String::new()
+                        } else {
+                            input.text
+                        };
+                        self.recheck(check);
+                        self.refresh_view();
+                    }
+                }
+                KeyCode::Esc => {}
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Edit(input);
+                }
+            },
+            Mode::Filter(mut input) => match key.code {
+                KeyCode::Enter => {}
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.refresh_view();
+                }
+                _ => {
+                    if input.handle_key(key) {
+                        self.filter = input.text.clone();
+                        self.refresh_view();
+                    }
+                    self.mode = Mode::Filter(input);
+                }
+            },
+            Mode::Find(mut input) => match key.code {
+                KeyCode::Enter if !input.text.is_empty() => {
+                    self.mode = Mode::Replace {
+                        pattern: input.text,
+                        input: Input::new(String::new()),
+                    };
+                }
+                KeyCode::Esc => {}
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Find(input);
+                }
+            },
+            Mode::Replace { pattern, mut input } => match key.code {
+                KeyCode::Enter => self.replace(&pattern, &input.text, check),
+                KeyCode::Esc => {}
+                _ => {
+                    input.handle_key(key);
+                    self.mode = Mode::Replace { pattern, input };
+                }
+            },
+            Mode::Confirm { action, .. } => {
+                if key.code == KeyCode::Char('y') {
+                    return action;
+                }
+            }
+        }
+
+        Action::Continue
+    }
+
+    fn browse(&mut self, key: KeyEvent, check: &mut impl FnMut(&mut [PlannedRename])) -> Action {
+        self.message = None;
+        let page = self.page.max(1) as isize;
+
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-page),
+            KeyCode::PageDown => self.move_cursor(page),
+            KeyCode::Home | KeyCode::Char('g') => self.move_cursor(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_cursor(isize::MAX),
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some(entry) = self.selected() {
+                    self.mode = Mode::Edit(Input::new(shown_name(entry).to_string()));
+                }
+            }
+            KeyCode::Char('x') | KeyCode::Delete => {
+                if let Some(index) = self.selected_index() {
+                    self.entries[index].new_name.clear();
+                    self.recheck(check);
+                    self.refresh_view();
+                }
+            }
+            KeyCode::Char('/') => self.mode = Mode::Filter(Input::new(self.filter.clone())),
+            KeyCode::Char('r') => self.mode = Mode::Find(Input::new(String::new())),
+            KeyCode::Char('s') => {
+                self.sort = self.sort.next();
+                self.refresh_view();
+            }
+            KeyCode::Char('a') => self.confirm_apply(),
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.refresh_view();
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                let renamed = self.renames().len();
+                if renamed == 0 {
+                    return Action::Quit;
+                }
+                self.mode = Mode::Confirm {
+                    question: format!("Quit and discard {renamed} new name(s)? [y/n]"),
+                    action: Action::Quit,
+                };
+            }
+            _ => {}
+        }
+
+        Action::Continue
+    }
+
+    fn confirm_apply(&mut self) {
+        let renamed = self.renames().len();
+        if renamed == 0 {
+            self.message = Some("No new names to apply.".to_string());
+            return;
+        }
+
+        let problems = self
+            .statuses
+            .iter()
+            .filter(|status| matches!(status, Status::Problem(_)))
+            .count();
+        let question = if problems > 0 {
+            format!("Apply {renamed} new name(s)? {problems} will be skipped. [y/n]")
+        } else {
+            format!("Apply {renamed} new name(s)? [y/n]")
+        };
+
+        self.mode = Mode::Confirm {
+            question,
+            action: Action::Apply,
+        };
+    }
+
+    /// Replaces every match of `pattern` in the last component of the shown
+    /// entries' names, starting from their new name if they have one.
+    fn replace(
+        &mut self,
+        pattern: &str,
+        replacement: &str,
+        check: &mut impl FnMut(&mut [PlannedRename]),
+    ) {
+        let regex = match substitute::build_regex(pattern, false) {
+            Ok(regex) => regex,
+            Err(error) => {
+                self.message = Some(error.to_string());
+                return;
+            }
+        };
+
+        let mut replaced = 0;
+        for &index in &self.view {
+            let entry = &mut self.entries[index];
+            let Some(new_name) =
+                substitute::replace_file_name(&regex, shown_name(entry), replacement, true)
+            else {
+                continue;
+            };
+
+            entry.new_name = if new_name == entry.name {
+                String::new()
+            } else {
+                new_name
+            };
+            replaced += 1;
+        }
+
+        self.message = Some(format!("Replaced {pattern:?} in {replaced} name(s)."));
+        self.recheck(check);
+        self.refresh_view();
+    }
+
+    /// Draws the table with a summary above it and the key help, a prompt
+    /// or the last message below it.
+    pub fn render(&mut self, frame: &mut Frame) {
+        let [header_area, table_area, footer_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let renamed = self.renames().len();
+        let problems = self
+            .statuses
+            .iter()
+            .filter(|status| matches!(status, Status::Problem(_)))
+            .count();
+        let mut summary = format!(
+            "{}  {} of {} shown, {renamed} renamed, {problems} problem(s), sorted by {}",
+            self.directory.display(),
+            self.view.len(),
+            self.entries.len(),
+            self.sort
+        );
+        if !self.filter.is_empty() {
+            summary.push_str(&format!(", filter {:?}", self.filter));
+        }
+        frame.render_widget(
+            Paragraph::new(summary).style(Style::new().add_modifier(Modifier::BOLD)),
+            header_area,
+        );
+
+        let rows = self.view.iter().map(|&index| {
+            let entry = &self.entries[index];
+            let (status, style) = match &self.statuses[index] {
+                Status::Unchanged => (String::new(), Style::new()),
+                Status::Renamed(new_name) if *new_name == entry.new_name => {
+                    ("ok".to_string(), Style::new().fg(Color::Green))
+                }
+                Status::Renamed(new_name) => {
+                    (format!("ok, as {new_name}"), Style::new().fg(Color::Green))
+                }
+                Status::Problem(reason) => (reason.clone(), Style::new().fg(Color::Red)),
+            };
+
+            Row::new([
+                Cell::from((index + 1).to_string()),
+                Cell::from(entry.name.as_str()),
+                Cell::from(entry.new_name.as_str()),
+                Cell::from(entry.kind.as_str()),
+                Cell::from(status),
+            ])
+            .style(style)
+        });
+
+        let width = self.entries.len().to_string().len().max(1) as u16;
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(width),
+                Constraint::Percentage(30),
+                Constraint::Percentage(30),
+                Constraint::Length(4),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(
+            Row::new(["#", "Old name", "New name", "Type", "Status"])
+                .style(Style::new().add_modifier(Modifier::UNDERLINED)),
+        )
+        .block(Block::bordered())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        // The border and the header row take three lines.
+        self.page = usize::from(table_area.height.saturating_sub(3));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let (prompt, input) = match &self.mode {
+            Mode::Browse => {
+                let footer = self.message.as_deref().unwrap_or(HELP);
+                frame.render_widget(Paragraph::new(footer), footer_area);
+                return;
+            }
+            Mode::Confirm { question, .. } => {
+                frame.render_widget(Paragraph::new(question.as_str()), footer_area);
+                return;
+            }
+            Mode::Edit(input) => ("New name: ", input),
+            Mode::Filter(input) => ("Filter: ", input),
+            Mode::Find(input) => ("Find (regex): ", input),
+            Mode::Replace { input, .. } => ("Replace with: ", input),
+        };
+
+        frame.render_widget(
+            Paragraph::new(format!("{prompt}{}", input.text)),
+            footer_area,
+        );
+        let cursor = Line::from(format!("{prompt}{}", input.before_cursor())).width() as u16;
+        frame.set_cursor_position(Position::new(
+            footer_area.x + cursor.min(footer_area.width.saturating_sub(1)),
+            footer_area.y,
+        ));
+    }
+}
+
+/// Whether an entry was given a new name.
+fn is_renamed(entry: &ListedEntry) -> bool {
+    !entry.new_name.is_empty() && entry.new_name != entry.name
+}
+
+/// The name an entry ends up with: its new name if it has one.
+fn shown_name(entry: &ListedEntry) -> &str {
+    if entry.new_name.is_empty() {
+        &entry.name
+    } else {
+        &entry.new_name
+    }
+}
+
+/// Runs a session on the terminal until the user quits or applies it, and
+/// returns the renames to apply, numbered by row, if they did.
+pub fn run(
+    mut session: Session,
+    mut check: impl FnMut(&mut [PlannedRename]),
+) -> Result<Option<Vec<(usize, ListedEntry)>>, Error> {
+    let mut terminal = ratatui::try_init().map_err(Error::Terminal)?;
+    let action = event_loop(&mut terminal, &mut session, &mut check);
+    ratatui::restore();
+
+    match action.map_err(Error::Terminal)? {
+        Action::Apply => Ok(Some(session.renames())),
+        Action::Quit | Action::Continue => Ok(None),
+    }
+}
+
+fn event_loop(
+    terminal: &mut DefaultTerminal,
+    session: &mut Session,
+    check: &mut impl FnMut(&mut [PlannedRename]),
+) -> std::io::Result<Action> {
+    loop {
+        terminal.draw(|frame| session.render(frame))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match session.handle_key(key, check) {
+                Action::Continue => {}
+                action => return Ok(action),
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The filesystem rules a new name is checked against, chosen with
+/// `--profile`. `portable` accepts only names every other profile accepts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Posix,
+    Windows,
+    Macos,
+    Portable,
+}
+
+/// The longest name every profile allows, in bytes or, on Windows, UTF-16
+/// code units.
+const MAX_LENGTH: usize = 255;
+
+/// Device names Windows reserves in every folder, with or without an
+/// extension.
+const RESERVED: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+impl Profile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Profile::Posix => "posix",
+            Profile::Windows => "windows",
+            Profile::Macos => "macos",
+            Profile::Portable => "portable",
+        }
+    }
+
+    fn windows_rules(self) -> bool {
+        matches!(self, Profile::Windows | Profile::Portable)
+    }
+
+    fn forbids(self, c: char) -> bool {
+        match c {
+            '\0' | '/' => true,
+            ':' => self != Profile::Posix,
+            '<' | '>' | '"' | '\\' | '|' | '?' | '*' => self.windows_rules(),
+            c if c.is_ascii_control() => self.windows_rules(),
+            _ => false,
+        }
+    }
+
+    /// How much of the length limit `c` uses. A name never has more UTF-16
+    /// code units than UTF-8 bytes, so portable counts bytes.
+    fn width(self, c: char) -> usize {
+        match self {
+            Profile::Windows => c.len_utf16(),
+            _ => c.len_utf8(),
+        }
+    }
+
+    fn length(self, name: &str) -> usize {
+        name.chars().map(|c| self.width(c)).sum()
+    }
+
+    fn unit(self) -> &'static str {
+        match self {
+            Profile::Windows => "UTF-16 code units",
+            _ => "bytes",
+        }
+    }
+}
+
+impl Default for Profile {
+    /// The profile of the platform the tool runs on.
+    fn default() -> Profile {
+        if cfg!(windows) {
+            Profile::Windows
+        } else if cfg!(target_os = "macos") {
+            Profile::Macos
+        } else {
+            Profile::Posix
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Profile, ()> {
+        match value {
+            "posix" => Ok(Profile::Posix),
+            "windows" => Ok(Profile::Windows),
+            "macos" => Ok(Profile::Macos),
+            "portable" => Ok(Profile::Portable),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Why a name is not valid on a profile.
+pub enum Problem {
+    Empty,
+    /// `.`, `..` or a Windows device name.
+    Reserved(String),
+    Character(char),
+    TrailingDotOrSpace,
+    TooLong {
+        length: usize,
+        unit: &'static str,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Empty => write!(f, "the name is empty"),
+            Problem::Reserved(name) => write!(f, "{name:?} is a reserved name"),
+            Problem::Character(c) => write!(f, "contains {c:?}"),
+            Problem::TrailingDotOrSpace => write!(f, "ends with a dot or a space"),
+            Problem::TooLong { length, unit } => {
+                write!(
+                    f,
+                    "is {length} {unit} long, at most {MAX_LENGTH} are allowed"
+                )
+            }
+        }
+    }
+}
+
+/// The first reason a single name, without any `/`, is not valid on
+/// `profile`, or `None` if it is.
+pub fn check_name(name: &str, profile: Profile) -> Option<Problem> {
+    if name.is_empty() {
+        return Some(Problem::Empty);
+    }
+    if name == "." || name == ".." {
+        return Some(Problem::Reserved(name.to_string()));
+    }
+    if let Some(c) = name.chars().find(|&c| profile.forbids(c)) {
+        return Some(Problem::Character(c));
+    }
+    if profile.windows_rules() {
+        if name.ends_with(['.', ' ']) {
+            return Some(Problem::TrailingDotOrSpace);
+        }
+        if is_device_name(name) {
+            return Some(Problem::Reserved(name.to_string()));
+        }
+    }
+
+    let length = profile.length(name);
+    if length > MAX_LENGTH {
+        return Some(Problem::TooLong {
+            length,
+            unit: profile.unit(),
+        });
+    }
+
+    None
+}
+
+/// Whether Windows treats `name` as a device, as it does `con`, `NUL.txt`
+/// and `COM1`.
+fn is_device_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+
+    let numbered = ["COM", "LPT"].iter().any(|prefix| {
+        stem.len() == 4
+            && stem.is_ascii()
+            && stem[..3].eq_ignore_ascii_case(prefix)
+            && stem[3..].chars().all(|c| c.is_ascii_digit())
+    });
+
+    numbered
+        || RESERVED
+            .iter()
+            .any(|reserved| stem.eq_ignore_ascii_case(reserved))
+}
+
+/// A version of `name` that is valid on `profile`: forbidden characters
+/// become `_`, trailing dots and spaces are dropped where Windows would drop
+/// them, device names get a `_` after their stem, and names that are too long
+/// are shortened before their extension.
+pub fn sanitize(name: &str, profile: Profile) -> String {
+    let mut name: String = name
+        .chars()
+        .map(|c| if profile.forbids(c) { '_' } else { c })
+        .collect();
+
+    if profile.windows_rules() {
+        name.truncate(name.trim_end_matches(['.', ' ']).len());
+    }
+    if name.is_empty() || name == "." || name == ".." {
+        name = "_".repeat(name.len().max(1));
+    }
+    if profile.windows_rules() && is_device_name(&name) {
+        let stem_end = name.find('.').unwrap_or(name.len());
+        name.insert(stem_end, '_');
+    }
+
+    shorten(&name, profile)
+}
+
+fn shorten(name: &str, profile: Profile) -> String {
+    if profile.length(name) <= MAX_LENGTH {
+        return name.to_string();
+    }
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && profile.length(extension) < 32 => {
+            (stem, &name[stem.len()..])
+        }
+        _ => (name, ""),
+    };
+
+    let mut budget = MAX_LENGTH - profile.length(extension);
+    let mut shortened: String = stem
+        .chars()
+        .take_while(|&c| {
+            let fits = profile.width(c) <= budget;
+            budget = budget.saturating_sub(profile.width(c));
+            fits
+        })
+        .collect();
+
+    // Cutting the stem short may leave it ending in a dot or a space.
+    if profile.windows_rules() && extension.is_empty() {
+        shortened.truncate(shortened.trim_end_matches(['.', ' ']).len());
+    }
+
+    shortened + extension
+}
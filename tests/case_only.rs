@@ -0,0 +1,94 @@
+mod common;
+
+use common::{names, plan_with, run};
+use rename_tool::apply::StopPolicy;
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::plan::{self, CheckOptions, PlannedRename, SkipReason};
+
+fn plan(fs: &MemoryFs, csv: &str, nfc: bool) -> Vec<PlannedRename> {
+    let options = CheckOptions {
+        nfc,
+        ..CheckOptions::default()
+    };
+    plan_with(fs, csv, &options)
+}
+
+#[test]
+fn renames_case_only_through_a_temporary_name() {
+    let fs = MemoryFs::case_insensitive();
+    fs.create_dir_all("/share/Project/notes");
+    let plan = plan(&fs, "old_name,new_name\nProject,project\n", false);
+
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+    assert_eq!(plan.len(), 2);
+
+    run(&fs, &plan, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["project", "project/notes"]);
+}
+
+#[test]
+fn keeps_refusing_a_different_entry_with_the_same_name() {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/Project");
+    fs.create_dir_all("/share/project");
+    let plan = plan(&fs, "old_name,new_name\nProject,project\n", false);
+
+    assert!(matches!(plan[0].skip, Some(SkipReason::TargetExists(_))));
+}
+
+#[test]
+fn case_only_rename_on_a_case_sensitive_filesystem_is_a_plain_rename() {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/Project");
+    let plan = plan(&fs, "old_name,new_name\nProject,project\n", false);
+
+    assert_eq!(plan.len(), 1);
+    run(&fs, &plan, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["project"]);
+}
+
+#[test]
+fn normalizes_new_names_to_nfc() {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/cafe\u{301}");
+    let plan = plan(&fs, "old_name,new_name\ncafe\u{301},cafe\u{301}\n", true);
+
+    assert_eq!(plan[0].new_name, "caf\u{e9}");
+    run(&fs, &plan, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["caf\u{e9}"]);
+}
+
+#[test]
+fn normalization_only_rename_on_an_insensitive_filesystem() {
+    let fs = MemoryFs::case_insensitive();
+    fs.create_dir_all("/share/cafe\u{301}");
+    let plan = plan(&fs, "old_name,new_name\ncafe\u{301},caf\u{e9}\n", false);
+
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+    run(&fs, &plan, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["caf\u{e9}"]);
+}
+
+#[test]
+fn unchanged_after_normalization_is_skipped() {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/caf\u{e9}");
+    let plan = plan(&fs, "old_name,new_name\ncaf\u{e9},cafe\u{301}\n", true);
+
+    assert!(matches!(plan[0].skip, Some(SkipReason::Unchanged)));
+}
+
+#[test]
+fn undoes_a_case_only_rename() {
+    let fs = MemoryFs::case_insensitive();
+    fs.create_dir_all("/share/Project");
+    let plan = plan(&fs, "old_name,new_name\nProject,project\n", false);
+    run(&fs, &plan, StopPolicy::Continue);
+
+    let mut undo: Vec<PlannedRename> = plan.iter().rev().map(PlannedRename::reversed).collect();
+    plan::check_sequential(&fs, &mut undo);
+    assert!(undo.iter().all(|rename| rename.skip.is_none()));
+
+    run(&fs, &undo, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["Project"]);
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A fresh folder holding a directory `d` with the folders `dirs`, and
+/// `plan.csv` with `csv` next to it.
+fn setup(name: &str, dirs: &[&str], csv: &str) -> PathBuf {
+    let base = std::env::temp_dir().join(format!("rename_tool_{}_{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    for dir in dirs {
+        fs::create_dir_all(base.join("d").join(dir)).unwrap();
+    }
+    fs::create_dir_all(base.join("d")).unwrap();
+    fs::write(base.join("plan.csv"), csv).unwrap();
+    base
+}
+
+/// Runs `rename_tool import` on the folder `setup` made, returning its exit
+/// code.
+fn import(base: &Path, flags: &[&str]) -> i32 {
+    Command::new(env!("CARGO_BIN_EXE_rename_tool"))
+        .current_dir(base)
+        .arg("import")
+        .args(flags)
+        .args(["--journal", "journal.csv", "d", "plan.csv"])
+        .output()
+        .unwrap()
+        .status
+        .code()
+        .unwrap()
+}
+
+fn exists(base: &Path, name: &str) -> bool {
+    base.join("d").join(name).is_dir()
+}
+
+#[test]
+fn exits_0_when_every_requested_rename_ran() {
+    let base = setup(
+        "requested",
+        &["a", "b", "c"],
+        "old_name,new_name\na,x\nb,\nc,c\n",
+    );
+
+    assert_eq!(import(&base, &["--dry-run"]), 0);
+    assert_eq!(import(&base, &[]), 0);
+    assert!(exists(&base, "x"));
+}
+
+#[test]
+fn exits_2_when_some_rows_were_skipped() {
+    let base = setup("partial", &["a"], "old_name,new_name\na,x\nmissing,y\n");
+
+    assert_eq!(import(&base, &["--dry-run"]), 3);
+    assert_eq!(import(&base, &[]), 2);
+    assert!(exists(&base, "x"));
+}
+
+#[test]
+fn exits_3_when_nothing_could_be_renamed() {
+    let base = setup("nothing", &["a"], "old_name,new_name\nmissing,y\n");
+
+    assert_eq!(import(&base, &[]), 3);
+    assert!(exists(&base, "a"));
+}
+
+#[test]
+fn strict_and_atomic_refuse_to_start_on_a_skipped_row() {
+    for flag in ["--strict", "--atomic"] {
+        let base = setup(
+            "refused",
+            &["a", "b"],
+            "old_name,new_name\na,x\nb,\nmissing,y\n",
+        );
+
+        assert_eq!(import(&base, &[flag]), 3, "{flag}");
+        assert!(exists(&base, "a"), "{flag}");
+    }
+}
+
+#[test]
+fn strict_and_atomic_run_rows_that_request_no_rename() {
+    for flag in ["--strict", "--atomic"] {
+        let base = setup(
+            "unrequested",
+            &["a", "b", "c"],
+            "old_name,new_name\na,x\nb,\nc,c\n",
+        );
+
+        assert_eq!(import(&base, &[flag]), 0, "{flag}");
+        assert!(exists(&base, "x"), "{flag}");
+    }
+}
@@ -0,0 +1,91 @@
+//! Helpers for the tests that import a plan into a `MemoryFs` and run it.
+//! Each test file uses only some of them.
+#![allow(dead_code)]
+
+use std::path::{Path, PathBuf};
+
+use rename_tool::apply::{self, Outcome, StopPolicy};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::import::{self, CsvOptions};
+use rename_tool::plan::{self, CheckOptions, PlannedRename, SkipReason};
+
+pub const DIRECTORY: &str = "/share";
+pub const CSV: &str = "/plan.csv";
+
+/// A `DIRECTORY` holding the folders `dirs`, with their parents.
+pub fn tree(dirs: &[&str]) -> MemoryFs {
+    let fs = MemoryFs::new();
+    fs.create_dir_all(DIRECTORY);
+    for dir in dirs {
+        fs.create_dir_all(path(dir));
+    }
+    fs
+}
+
+/// Reads `csv` as a plan for `DIRECTORY`, before any of its checks.
+pub fn read_plan(fs: &MemoryFs, csv: &str) -> Vec<PlannedRename> {
+    fs.write(CSV, csv);
+    import::read_plan(
+        fs,
+        Path::new(DIRECTORY),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    )
+    .unwrap()
+    .plan
+}
+
+/// Reads, checks and orders `csv` the way `rename_tool import` does.
+pub fn plan(fs: &MemoryFs, csv: &str) -> Vec<PlannedRename> {
+    plan_with(fs, csv, &CheckOptions::default())
+}
+
+/// `plan` with the checks `options` ask for.
+pub fn plan_with(fs: &MemoryFs, csv: &str, options: &CheckOptions) -> Vec<PlannedRename> {
+    let mut plan = read_plan(fs, csv);
+    plan::check(fs, Path::new(DIRECTORY), &mut plan, options);
+    plan
+}
+
+/// Runs `plan`, returning the outcome of each row in the order they ran.
+pub fn run(fs: &MemoryFs, plan: &[PlannedRename], stop: StopPolicy) -> Vec<(usize, Outcome)> {
+    let mut outcomes = Vec::new();
+    apply::execute(fs, plan, None, stop, |rename, outcome| {
+        outcomes.push((rename.row, clone_outcome(outcome)));
+    })
+    .unwrap();
+    outcomes
+}
+
+fn clone_outcome(outcome: &Outcome) -> Outcome {
+    match outcome {
+        Outcome::Renamed => Outcome::Renamed,
+        Outcome::Skipped { code, message } => Outcome::Skipped {
+            code,
+            message: message.clone(),
+        },
+        Outcome::Failed(error) => Outcome::Failed(error.clone()),
+        Outcome::RolledBack => Outcome::RolledBack,
+        Outcome::RollbackFailed(error) => Outcome::RollbackFailed(error.clone()),
+    }
+}
+
+pub fn skip_of(plan: &[PlannedRename], row: usize) -> Option<&SkipReason> {
+    plan.iter()
+        .find(|rename| rename.row == row)
+        .and_then(|rename| rename.skip.as_ref())
+}
+
+/// Every path inside `DIRECTORY`, relative to it and sorted.
+pub fn names(fs: &MemoryFs) -> Vec<String> {
+    fs.paths()
+        .iter()
+        .filter_map(|path| path.strip_prefix(DIRECTORY).ok())
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+pub fn path(name: &str) -> PathBuf {
+    Path::new(DIRECTORY).join(name)
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::path::Path;
+
+use common::{DIRECTORY, plan_with, read_plan, run, tree};
+use rename_tool::apply::StopPolicy;
+use rename_tool::filesystem::FileSystem;
+use rename_tool::plan::{self, CheckOptions, PlannedRename, SkipReason};
+
+fn plan(csv: &str) -> Vec<PlannedRename> {
+    let fs = tree(&["alpha/inner"]);
+    fs.create_dir_all("/elsewhere");
+    fs.create_dir_all("/etc/config");
+
+    let mut plan = read_plan(&fs, csv);
+    plan::check_rows(
+        &fs,
+        Path::new(DIRECTORY),
+        &mut plan,
+        &CheckOptions::default(),
+    );
+    plan
+}
+
+fn outside(plan: &[PlannedRename]) -> Vec<String> {
+    plan.iter()
+        .map(|rename| match &rename.skip {
+            Some(SkipReason::OutsideDirectory(path)) => path.display().to_string(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+#[test]
+fn keeps_rows_inside_the_directory() {
+    let plan = plan("old_name,new_name\nalpha,beta\nalpha/inner,alpha/outer\n");
+
+    assert_eq!(outside(&plan), ["", ""]);
+}
+
+#[test]
+fn refuses_rows_that_climb_out() {
+    let plan =
+        plan("old_name,new_name\n../elsewhere,../moved\nalpha,../../taken\nalpha/../..,gone\n");
+
+    assert_eq!(outside(&plan), ["/elsewhere", "/taken", "/"]);
+}
+
+#[test]
+fn refuses_absolute_paths_and_the_directory_itself() {
+    let plan = plan("old_name,new_name\n/etc/config,/etc/hijacked\nalpha/..,renamed\n");
+
+    assert_eq!(outside(&plan), ["/etc/config", "/share"]);
+}
+
+#[test]
+fn moves_outside_when_allowed() {
+    let fs = tree(&["alpha", "beta"]);
+    fs.create_dir_all("/elsewhere");
+    let options = CheckOptions {
+        allow_move_outside: true,
+        create_parents: true,
+        ..CheckOptions::default()
+    };
+    let plan = plan_with(
+        &fs,
+        "old_name,new_name\nalpha,../out/a\nbeta,/elsewhere/new/b\n",
+        &options,
+    );
+
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert!(fs.is_dir(Path::new("/out/a")));
+    assert!(fs.is_dir(Path::new("/elsewhere/new/b")));
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use rename_tool::dialect::{self, Encoding};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::import::{self, CsvOptions};
+
+fn utf16le(text: &str, bom: bool) -> Vec<u8> {
+    let mut bytes = if bom { vec![0xff, 0xfe] } else { Vec::new() };
+    bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    bytes
+}
+
+/// The old and new names `import` reads from `contents`.
+fn read(contents: impl Into<Vec<u8>>, options: &CsvOptions) -> Vec<(String, String)> {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share");
+    fs.write("/plan.csv", contents);
+
+    import::read_plan(&fs, Path::new("/share"), Path::new("/plan.csv"), options)
+        .unwrap()
+        .plan
+        .into_iter()
+        .map(|rename| (rename.old_name, rename.new_name))
+        .collect()
+}
+
+fn pairs(names: &[(&str, &str)]) -> Vec<(String, String)> {
+    names
+        .iter()
+        .map(|(old_name, new_name)| (old_name.to_string(), new_name.to_string()))
+        .collect()
+}
+
+#[test]
+fn detects_the_encoding() {
+    assert_eq!(
+        dialect::decode(b"\xef\xbb\xbfcaf\xc3\xa9"),
+        ("café".to_string(), Encoding::Utf8)
+    );
+    assert_eq!(
+        dialect::decode(&utf16le("café", true)),
+        ("café".to_string(), Encoding::Utf16Le)
+    );
+    assert_eq!(
+        dialect::decode(&utf16le("old_name", false)),
+        ("old_name".to_string(), Encoding::Utf16Le)
+    );
+    assert_eq!(
+        dialect::decode(b"caf\xe9 \x80"),
+        ("café €".to_string(), Encoding::Windows1252)
+    );
+}
+
+#[test]
+fn detects_the_delimiter_outside_quotes() {
+    assert_eq!(
+        dialect::detect_delimiter("old_name;new_name\na,b", b'"'),
+        b';'
+    );
+    assert_eq!(dialect::detect_delimiter("\"a;b;c\",new\n;;;", b'"'), b',');
+    assert_eq!(dialect::detect_delimiter("old\tnew", b'"'), b'\t');
+    assert_eq!(dialect::detect_delimiter("old_name", b'"'), b',');
+}
+
+#[test]
+fn reads_spreadsheet_dialects() {
+    let expected = pairs(&[("café", "bar; ou café")]);
+
+    assert_eq!(
+        read(
+            b"old_name;new_name\r\ncaf\xe9;\"bar; ou caf\xe9\"\r\n".to_vec(),
+            &CsvOptions::default()
+        ),
+        expected
+    );
+    assert_eq!(
+        read(
+            utf16le("old_name\tnew_name\r\ncafé\tbar; ou café\r\n", true),
+            &CsvOptions::default()
+        ),
+        expected
+    );
+
+    let options = CsvOptions {
+        delimiter: Some(b'|'),
+        quote: Some(b'\''),
+        ..CsvOptions::default()
+    };
+    assert_eq!(
+        read("old_name|new_name\ncafé|'bar; ou café'\n", &options),
+        expected
+    );
+}
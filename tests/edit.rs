@@ -0,0 +1,45 @@
+use rename_tool::edit::parse_edit_file;
+
+const HEADER: &str = "# Edit the names after the numbers, then save and quit.\n";
+
+#[test]
+fn reads_unchanged_and_edited_lines() {
+    let edited = format!("{HEADER}1 alpha\n2 gamma\n\n3 beta\n");
+
+    assert_eq!(
+        parse_edit_file(&edited, 3).unwrap(),
+        ["alpha", "gamma", "beta"]
+    );
+}
+
+#[test]
+fn keeps_spaces_in_names() {
+    let edited = "1  lead\r\n2 trail \r\n3 two  spaces\r\n4 \n";
+
+    assert_eq!(
+        parse_edit_file(edited, 4).unwrap(),
+        [" lead", "trail ", "two  spaces", ""]
+    );
+}
+
+#[test]
+fn refuses_swapped_lines() {
+    let error = parse_edit_file("2 beta\n1 alpha\n", 2).unwrap_err();
+
+    assert!(error.contains("Entry 1 is missing"), "{error}");
+}
+
+#[test]
+fn refuses_removed_and_added_lines() {
+    let error = parse_edit_file("1 alpha\n3 gamma\n", 3).unwrap_err();
+    assert!(error.contains("Entry 2 is missing"), "{error}");
+
+    let error = parse_edit_file("1 alpha\n2 beta\n", 3).unwrap_err();
+    assert!(error.contains("Entry 3 is missing"), "{error}");
+
+    let error = parse_edit_file("1 alpha\ndelta\n2 beta\n", 2).unwrap_err();
+    assert!(error.contains("lines must not be added"), "{error}");
+
+    let error = parse_edit_file("1 alpha\n2 beta\n3 gamma\n", 2).unwrap_err();
+    assert!(error.contains("unknown entry 3"), "{error}");
+}
@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rename_tool::Error;
+use rename_tool::columns::{Column, Columns, Value};
+use rename_tool::entry::{Context, EntryKind, TypeFilter};
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::{DirEntry, DiskFs, FileSystem, MemoryFs, Metadata};
+
+fn tree() -> MemoryFs {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/clients/acme/2023");
+    fs.create_dir_all("/share/archive");
+    fs.write("/share/clients/acme/report.pdf", "");
+    fs.write("/share/readme.txt", "");
+    fs
+}
+
+fn listed_names(fs: &MemoryFs, options: &ExportOptions) -> Vec<String> {
+    export::list_entries(fs, Path::new("/share"), options)
+        .unwrap()
+        .entries
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect()
+}
+
+#[test]
+fn lists_immediate_folders_by_default() {
+    assert_eq!(
+        listed_names(&tree(), &ExportOptions::default()),
+        ["archive", "clients"]
+    );
+}
+
+#[test]
+fn lists_nested_folders_as_relative_paths() {
+    let options = ExportOptions {
+        recursive: true,
+        ..ExportOptions::default()
+    };
+
+    assert_eq!(
+        listed_names(&tree(), &options),
+        ["archive", "clients", "clients/acme", "clients/acme/2023"]
+    );
+}
+
+#[test]
+fn stops_at_max_depth() {
+    let options = ExportOptions {
+        max_depth: Some(2),
+        ..ExportOptions::default()
+    };
+
+    assert_eq!(
+        listed_names(&tree(), &options),
+        ["archive", "clients", "clients/acme"]
+    );
+}
+
+#[test]
+fn filters_by_type() {
+    let options = ExportOptions {
+        recursive: true,
+        type_filter: TypeFilter::Files,
+        ..ExportOptions::default()
+    };
+    assert_eq!(
+        listed_names(&tree(), &options),
+        ["clients/acme/report.pdf", "readme.txt"]
+    );
+
+    let fs = tree();
+    let listing = export::list_entries(
+        &fs,
+        Path::new("/share"),
+        &ExportOptions {
+            type_filter: TypeFilter::All,
+            ..ExportOptions::default()
+        },
+    )
+    .unwrap();
+    let kinds: Vec<EntryKind> = listing.entries.iter().map(|entry| entry.kind).collect();
+    assert!(kinds == [EntryKind::Dir, EntryKind::Dir, EntryKind::File]);
+}
+
+#[test]
+fn rejects_a_missing_directory() {
+    let result = export::list_entries(&tree(), Path::new("/elsewhere"), &ExportOptions::default());
+
+    assert!(matches!(result, Err(Error::NotADirectory(_))));
+}
+
+#[test]
+fn reads_metadata_columns() {
+    let fs = tree();
+    fs.write("/share/clients/acme/report.pdf", "12345");
+    let columns = Columns::new(vec![
+        Column::Size,
+        Column::Items,
+        Column::Permissions,
+        Column::Owner,
+    ]);
+
+    assert_eq!(columns.headers(), ["size", "items", "permissions", "owner"]);
+    assert_eq!(
+        columns.values(&fs, Path::new("/share/clients")),
+        [
+            Value::Number(5),
+            Value::Number(3),
+            Value::Text("rwxr-xr-x".to_string()),
+            Value::Empty,
+        ]
+    );
+    assert_eq!(
+        columns.values(&fs, Path::new("/share/readme.txt")),
+        [
+            Value::Number(0),
+            Value::Empty,
+            Value::Text("rw-r--r--".to_string()),
+            Value::Empty,
+        ]
+    );
+}
+
+#[test]
+fn formats_times_as_utc() {
+    let fs = tree();
+    let columns = Columns::new(vec![Column::Created]);
+
+    let values = columns.values(&fs, Path::new("/share/archive"));
+
+    assert!(matches!(values[..], [Value::Time(_)]));
+    assert!(values[0].to_string().starts_with("1970-01-01T00:00:"));
+    assert!(values[0].to_string().ends_with('Z'));
+}
+
+/// A `MemoryFs` that records every folder it is asked to read.
+struct CountingFs {
+    fs: MemoryFs,
+    reads: RefCell<Vec<PathBuf>>,
+}
+
+impl FileSystem for CountingFs {
+    fn kind(&self, path: &Path) -> Option<EntryKind> {
+        self.fs.kind(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        self.reads.borrow_mut().push(path.to_path_buf());
+        self.fs.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.fs.metadata(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.fs.rename(from, to)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        self.fs.create_dir(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        self.fs.remove_dir(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.fs.read(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fs.canonicalize(path)
+    }
+}
+
+#[test]
+fn measures_each_folder_once() {
+    let fs = tree();
+    fs.write("/share/clients/acme/2023/q1.pdf", "123");
+    let options = ExportOptions {
+        recursive: true,
+        ..ExportOptions::default()
+    };
+    let listing = export::list_entries(&fs, Path::new("/share"), &options).unwrap();
+    let fs = CountingFs {
+        fs,
+        reads: RefCell::default(),
+    };
+    let columns = Columns::new(vec![Column::Size, Column::Items]);
+
+    let values: Vec<_> = listing
+        .entries
+        .iter()
+        .map(|entry| (entry.name.as_str(), columns.values(&fs, &entry.path)))
+        .collect();
+
+    assert_eq!(
+        values,
+        [
+            ("archive", vec![Value::Number(0), Value::Number(0)]),
+            ("clients", vec![Value::Number(3), Value::Number(4)]),
+            ("clients/acme", vec![Value::Number(3), Value::Number(3)]),
+            (
+                "clients/acme/2023",
+                vec![Value::Number(3), Value::Number(1)]
+            ),
+        ]
+    );
+    // archive, clients, clients/acme and clients/acme/2023, once each.
+    assert_eq!(fs.reads.borrow().len(), 4);
+}
+
+#[cfg(unix)]
+#[test]
+fn does_not_measure_through_symlinked_folders() {
+    let base = std::env::temp_dir().join(format!("rename_tool_{}_symlink", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("real")).unwrap();
+    std::fs::write(base.join("real/data.bin"), "12345").unwrap();
+    std::os::unix::fs::symlink(base.join("real"), base.join("link")).unwrap();
+
+    let real = Context::of(&DiskFs, &base.join("real")).unwrap();
+    let link = Context::of(&DiskFs, &base.join("link")).unwrap();
+
+    assert_eq!((real.items, real.size), (Some(1), 5));
+    assert!(matches!(link.kind, EntryKind::Dir));
+    assert_eq!(link.items, None);
+    assert_ne!(link.size, 5);
+    std::fs::remove_dir_all(&base).unwrap();
+}
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use rename_tool::Error;
+use rename_tool::entry::TypeFilter;
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::{FileSystem, MemoryFs};
+use rename_tool::fingerprint::{self, Staleness, Stamp};
+use rename_tool::import::{self, CsvOptions};
+
+const DIRECTORY: &str = "/share/projects";
+const CSV: &str = "/share/plan.csv";
+
+fn tree() -> MemoryFs {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/projects/alpha");
+    fs.create_dir_all("/share/projects/beta");
+    fs.write("/share/projects/notes.txt", "draft");
+    fs
+}
+
+/// Exports the directory the way `rename_tool export --type all` does, minus
+/// the rows for `left_out`.
+fn export(fs: &MemoryFs, left_out: &[&str]) {
+    let options = ExportOptions {
+        type_filter: TypeFilter::All,
+        ..ExportOptions::default()
+    };
+    let listing = export::list_entries(fs, Path::new(DIRECTORY), &options).unwrap();
+
+    let mut csv = format!("old_name,new_name,type,{}\n", listing.stamp);
+    for entry in &listing.entries {
+        if !left_out.contains(&entry.name.as_str()) {
+            let fingerprint = entry.fingerprint.unwrap();
+            csv.push_str(&format!("{},,{},{fingerprint}\n", entry.name, entry.kind));
+        }
+    }
+    fs.write(CSV, csv);
+}
+
+fn staleness(fs: &MemoryFs) -> Staleness {
+    let csv = import::read_plan(
+        fs,
+        Path::new(DIRECTORY),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    )
+    .unwrap();
+    let stamp = csv.stamp.expect("the export wrote a stamp");
+    fingerprint::check(fs, Path::new(DIRECTORY), Path::new(CSV), &stamp, &csv.plan).unwrap()
+}
+
+#[test]
+fn unchanged_directory_is_not_stale() {
+    let fs = tree();
+    export(&fs, &[]);
+
+    assert!(!staleness(&fs).stale);
+}
+
+#[test]
+fn rows_left_out_of_the_csv_are_not_a_change() {
+    let fs = tree();
+    export(&fs, &["beta"]);
+
+    assert!(!staleness(&fs).stale);
+}
+
+#[test]
+fn reports_modified_entries() {
+    let fs = tree();
+    export(&fs, &[]);
+    fs.write("/share/projects/notes.txt", "final version");
+
+    let staleness = staleness(&fs);
+    assert!(staleness.stale);
+    assert_eq!(staleness.changed, ["notes.txt"]);
+    assert!(staleness.vanished.is_empty());
+    assert!(staleness.unlisted.is_empty());
+}
+
+#[test]
+fn reports_entries_renamed_by_someone_else() {
+    let fs = tree();
+    export(&fs, &[]);
+    fs.rename(
+        Path::new("/share/projects/alpha"),
+        Path::new("/share/projects/gamma"),
+    )
+    .unwrap();
+
+    let staleness = staleness(&fs);
+    assert_eq!(staleness.vanished, ["alpha"]);
+    assert_eq!(staleness.unlisted, ["gamma"]);
+}
+
+#[test]
+fn reports_added_entries() {
+    let fs = tree();
+    export(&fs, &[]);
+    fs.create_dir_all("/share/projects/delta");
+
+    let staleness = staleness(&fs);
+    assert!(staleness.stale);
+    assert_eq!(staleness.unlisted, ["delta"]);
+}
+
+#[test]
+fn ignores_a_csv_kept_inside_the_directory() {
+    let fs = tree();
+    let csv = "/share/projects/plan.csv";
+    fs.write(csv, "");
+
+    let options = ExportOptions {
+        type_filter: TypeFilter::All,
+        ..ExportOptions::default()
+    };
+    let mut listing = export::list_entries(&fs, Path::new(DIRECTORY), &options).unwrap();
+    listing.exclude(Path::new(csv));
+    fs.write(
+        csv,
+        format!(
+            "old_name,new_name,type,{}
+alpha,gamma,dir,
+",
+            listing.stamp
+        ),
+    );
+
+    let imported = import::read_plan(
+        &fs,
+        Path::new(DIRECTORY),
+        Path::new(csv),
+        &CsvOptions::default(),
+    )
+    .unwrap();
+    let stamp = imported.stamp.unwrap();
+    let staleness = fingerprint::check(
+        &fs,
+        Path::new(DIRECTORY),
+        Path::new(csv),
+        &stamp,
+        &imported.plan,
+    )
+    .unwrap();
+
+    assert!(!staleness.stale);
+}
+
+#[test]
+fn csv_without_fingerprints_has_no_stamp() {
+    let fs = tree();
+    fs.write(CSV, "old_name,new_name\nalpha,gamma\n");
+
+    let csv = import::read_plan(
+        &fs,
+        Path::new(DIRECTORY),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    )
+    .unwrap();
+    assert!(csv.stamp.is_none());
+}
+
+#[test]
+fn rejects_a_damaged_stamp() {
+    let fs = tree();
+    fs.write(
+        CSV,
+        "old_name,new_name,fingerprint depth=1 hash=zz\nalpha,gamma,\n",
+    );
+
+    let result = import::read_plan(
+        &fs,
+        Path::new(DIRECTORY),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    );
+    assert!(matches!(result, Err(Error::InvalidFingerprint { .. })));
+}
+
+#[test]
+fn stamp_round_trips_through_its_header() {
+    let header = "fingerprint depth=all type=files hash=00000000000000ff";
+    let stamp: Stamp = header.parse().unwrap();
+
+    assert_eq!(stamp.max_depth, None);
+    assert_eq!(stamp.hash, 0xff);
+    assert_eq!(stamp.to_string(), header);
+}
@@ -0,0 +1,389 @@
+mod common;
+
+use std::path::Path;
+
+use common::{CSV, DIRECTORY, names, path, plan, plan_with, run, skip_of, tree};
+use rename_tool::Error;
+use rename_tool::apply::{Outcome, StopPolicy};
+use rename_tool::entry::{EntryKind, TypeFilter};
+use rename_tool::filesystem::FileSystem;
+use rename_tool::import::{self, CsvOptions};
+use rename_tool::plan::{self, CheckOptions, PlannedRename, SkipReason};
+
+#[test]
+fn renames_every_valid_row() {
+    let fs = tree(&["alpha", "beta"]);
+    let plan = plan(&fs, "old_name,new_name\nalpha,gamma\nbeta,delta\n");
+
+    let outcomes = run(&fs, &plan, StopPolicy::Continue);
+
+    assert!(
+        outcomes
+            .iter()
+            .all(|(_, outcome)| matches!(outcome, Outcome::Renamed))
+    );
+    assert_eq!(names(&fs), ["delta", "gamma"]);
+}
+
+#[test]
+fn ignores_metadata_columns() {
+    let fs = tree(&["alpha"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name,type,size,owner,modified\nalpha,beta,dir,0,root,2024-01-01T00:00:00Z\n",
+    );
+
+    assert!(skip_of(&plan, 2).is_none());
+    run(&fs, &plan, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["beta"]);
+}
+
+#[test]
+fn rejects_wrong_headers() {
+    let fs = tree(&["alpha"]);
+    fs.write(CSV, "old,new\nalpha,beta\n");
+
+    let result = import::read_plan(
+        &fs,
+        Path::new(DIRECTORY),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    );
+
+    assert!(matches!(result, Err(Error::InvalidHeaders { .. })));
+}
+
+#[test]
+fn finds_columns_by_header_in_any_order() {
+    for csv in [
+        "new_name,old_name\nbeta,alpha\n",
+        "\u{feff}Notes, New Name ,OLD-NAME,Type\n,beta,alpha,dir\n",
+    ] {
+        let fs = tree(&["alpha"]);
+        let plan = plan(&fs, csv);
+
+        assert!(skip_of(&plan, 2).is_none());
+        assert_eq!(plan[0].new_name, "beta");
+    }
+}
+
+#[test]
+fn reads_chosen_columns_and_files_without_headers() {
+    let fs = tree(&["alpha", "beta"]);
+    let options = CsvOptions {
+        old_column: Some("Folder".to_string()),
+        new_column: Some("3".to_string()),
+        ..CsvOptions::default()
+    };
+    fs.write(CSV, "folder,notes,rename to\nalpha,,gamma\n");
+    let chosen = import::read_plan(&fs, Path::new(DIRECTORY), Path::new(CSV), &options)
+        .unwrap()
+        .plan;
+    assert_eq!(
+        (chosen[0].old_name.as_str(), chosen[0].new_name.as_str()),
+        ("alpha", "gamma")
+    );
+
+    let options = CsvOptions {
+        no_header: true,
+        ..CsvOptions::default()
+    };
+    fs.write(CSV, "alpha,gamma\nbeta,delta\n");
+    let headerless = import::read_plan(&fs, Path::new(DIRECTORY), Path::new(CSV), &options)
+        .unwrap()
+        .plan;
+    let rows: Vec<(usize, &str)> = headerless
+        .iter()
+        .map(|rename| (rename.row, rename.old_name.as_str()))
+        .collect();
+    assert_eq!(rows, [(1, "alpha"), (2, "beta")]);
+
+    let options = CsvOptions {
+        old_column: Some("old_name".to_string()),
+        no_header: true,
+        ..CsvOptions::default()
+    };
+    let result = import::read_plan(&fs, Path::new(DIRECTORY), Path::new(CSV), &options);
+    assert!(matches!(result, Err(Error::InvalidHeaders { .. })));
+}
+
+#[test]
+fn rejects_missing_csv_and_directory() {
+    let fs = tree(&[]);
+
+    let missing_csv = import::read_plan(
+        &fs,
+        Path::new(DIRECTORY),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    );
+    assert!(matches!(missing_csv, Err(Error::NotACsvFile(_))));
+
+    fs.write(CSV, "old_name,new_name\n");
+    let missing_directory = import::read_plan(
+        &fs,
+        Path::new("/elsewhere"),
+        Path::new(CSV),
+        &CsvOptions::default(),
+    );
+    assert!(matches!(missing_directory, Err(Error::NotADirectory(_))));
+}
+
+#[test]
+fn skips_empty_names_and_invalid_types() {
+    let fs = tree(&["alpha", "beta"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name,type\nalpha,,dir\n,gamma,dir\nbeta,delta,folder\n",
+    );
+
+    assert!(matches!(skip_of(&plan, 2), Some(SkipReason::EmptyName)));
+    assert!(matches!(skip_of(&plan, 3), Some(SkipReason::EmptyName)));
+    assert!(matches!(
+        skip_of(&plan, 4),
+        Some(SkipReason::InvalidType(_))
+    ));
+}
+
+#[test]
+fn skips_missing_sources() {
+    let fs = tree(&["alpha"]);
+    let plan = plan(&fs, "old_name,new_name\nalpha,beta\nmissing,gamma\n");
+
+    assert!(skip_of(&plan, 2).is_none());
+    assert!(matches!(
+        skip_of(&plan, 3),
+        Some(SkipReason::MissingSource(source)) if *source == path("missing")
+    ));
+
+    let outcomes = run(&fs, &plan, StopPolicy::Continue);
+    assert!(
+        outcomes
+            .iter()
+            .any(|(row, outcome)| *row == 3 && matches!(outcome, Outcome::Skipped { .. }))
+    );
+    assert_eq!(names(&fs), ["beta"]);
+}
+
+#[test]
+fn skips_targets_that_already_exist() {
+    let fs = tree(&["alpha", "beta"]);
+    let plan = plan(&fs, "old_name,new_name\nalpha,beta\n");
+
+    assert!(matches!(
+        skip_of(&plan, 2),
+        Some(SkipReason::TargetExists(target)) if *target == path("beta")
+    ));
+
+    run(&fs, &plan, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["alpha", "beta"]);
+}
+
+#[test]
+fn skips_duplicate_sources_and_targets() {
+    let fs = tree(&["alpha", "beta", "gamma"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name\nalpha,delta\nalpha,epsilon\nbeta,zeta\ngamma,zeta\n",
+    );
+
+    assert!(skip_of(&plan, 2).is_none());
+    assert!(matches!(
+        skip_of(&plan, 3),
+        Some(SkipReason::DuplicateSource(2))
+    ));
+    assert!(skip_of(&plan, 4).is_none());
+    assert!(matches!(
+        skip_of(&plan, 5),
+        Some(SkipReason::DuplicateTarget(4))
+    ));
+}
+
+#[test]
+fn skipped_row_keeps_its_source_as_a_conflict() {
+    // beta cannot be renamed, so alpha must not be renamed onto it.
+    let fs = tree(&["alpha", "beta", "gamma"]);
+    let plan = plan(&fs, "old_name,new_name\nalpha,beta\nbeta,gamma\n");
+
+    assert!(matches!(
+        skip_of(&plan, 3),
+        Some(SkipReason::TargetExists(_))
+    ));
+    assert!(matches!(
+        skip_of(&plan, 2),
+        Some(SkipReason::TargetExists(_))
+    ));
+}
+
+#[test]
+fn skips_unchanged_names_and_moves_into_missing_folders() {
+    let fs = tree(&["alpha", "beta/inner"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name\nalpha,alpha\nbeta/inner,gamma/inner\n",
+    );
+
+    assert!(matches!(skip_of(&plan, 2), Some(SkipReason::Unchanged)));
+    assert!(matches!(
+        skip_of(&plan, 3),
+        Some(SkipReason::MissingParent(parent)) if *parent == path("gamma")
+    ));
+}
+
+#[test]
+fn checks_the_recorded_kind() {
+    let fs = tree(&["alpha"]);
+    fs.write(path("notes.txt"), "");
+    let plan = plan(
+        &fs,
+        "old_name,new_name,type\nalpha,beta,file\nnotes.txt,todo.txt,file\n",
+    );
+
+    assert!(matches!(
+        skip_of(&plan, 2),
+        Some(SkipReason::KindChanged(EntryKind::File, EntryKind::Dir))
+    ));
+    assert!(skip_of(&plan, 3).is_none());
+}
+
+#[test]
+fn renames_only_folders_by_default_unless_rows_name_their_type() {
+    let fs = tree(&[]);
+    fs.write(path("notes.txt"), "");
+    fs.write(path("todo.txt"), "");
+    let csv = "old_name,new_name,type\nnotes.txt,a.txt,file\ntodo.txt,b.txt,\n";
+
+    let plan = plan(&fs, csv);
+    assert!(skip_of(&plan, 2).is_none());
+    assert!(matches!(
+        skip_of(&plan, 3),
+        Some(SkipReason::ExcludedKind(EntryKind::File))
+    ));
+
+    let options = CheckOptions {
+        type_filter: Some(TypeFilter::Dirs),
+        ..CheckOptions::default()
+    };
+    let plan = plan_with(&fs, csv, &options);
+    assert!(matches!(
+        skip_of(&plan, 2),
+        Some(SkipReason::ExcludedKind(EntryKind::File))
+    ));
+}
+
+#[test]
+fn swaps_two_names() {
+    let fs = tree(&["alpha/a", "beta/b"]);
+    let plan = plan(&fs, "old_name,new_name\nalpha,beta\nbeta,alpha\n");
+
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["alpha", "alpha/b", "beta", "beta/a"]);
+}
+
+#[test]
+fn rotates_a_cycle_regardless_of_row_order() {
+    for csv in [
+        "old_name,new_name\na,b\nb,c\nc,a\n",
+        "old_name,new_name\nc,a\na,b\nb,c\n",
+    ] {
+        let fs = tree(&["a/1", "b/2", "c/3"]);
+        let plan = plan(&fs, csv);
+
+        run(&fs, &plan, StopPolicy::Continue);
+
+        assert_eq!(names(&fs), ["a", "a/3", "b", "b/1", "c", "c/2"]);
+    }
+}
+
+#[test]
+fn shifts_a_chain_from_its_free_end() {
+    let fs = tree(&["01_x", "02_x", "03_x"]);
+    let plan = plan(&fs, "old_name,new_name\n01_x,02_x\n02_x,03_x\n03_x,04_x\n");
+
+    let outcomes = run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(
+        outcomes.iter().map(|(row, _)| *row).collect::<Vec<_>>(),
+        [4, 3, 2]
+    );
+    assert_eq!(names(&fs), ["02_x", "03_x", "04_x"]);
+}
+
+#[test]
+fn renames_nested_folders_before_their_parents() {
+    let fs = tree(&["clients/acme"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name\nclients,customers\nclients/acme,clients/acme_corp\n",
+    );
+
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["customers", "customers/acme_corp"]);
+}
+
+#[test]
+fn temporary_names_avoid_existing_entries() {
+    let fs = tree(&["a", "b", ".rename_tool_tmp_2"]);
+    let plan = plan(&fs, "old_name,new_name\na,b\nb,a\n");
+
+    assert!(
+        plan.iter()
+            .all(|rename| rename.new_path != path(".rename_tool_tmp_2"))
+    );
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert!(fs.exists(&path(".rename_tool_tmp_2")));
+    assert!(!fs.exists(&path(".rename_tool_tmp_2_1")));
+}
+
+#[test]
+fn rolls_back_when_a_rename_does_not_complete() {
+    let fs = tree(&["alpha", "beta"]);
+    let mut plan = plan(&fs, "old_name,new_name\nalpha,gamma\nbeta,delta\n");
+
+    // Someone takes the second target after the plan was checked.
+    fs.create_dir_all(path("delta"));
+    plan.sort_by_key(|rename| rename.row);
+
+    let outcomes = run(&fs, &plan, StopPolicy::RollBack);
+
+    assert!(matches!(outcomes.last(), Some((2, Outcome::RolledBack))));
+    assert_eq!(names(&fs), ["alpha", "beta", "delta"]);
+}
+
+#[test]
+fn undo_reverses_a_run() {
+    let fs = tree(&["a/1", "b/2"]);
+    let plan = plan(&fs, "old_name,new_name\na,b\nb,a\n");
+    run(&fs, &plan, StopPolicy::Continue);
+
+    let mut undo: Vec<PlannedRename> = plan.iter().rev().map(PlannedRename::reversed).collect();
+    plan::check_sequential(&fs, &mut undo);
+    assert!(undo.iter().all(|rename| rename.skip.is_none()));
+
+    run(&fs, &undo, StopPolicy::Continue);
+    assert_eq!(names(&fs), ["a", "a/1", "b", "b/2"]);
+}
+
+#[test]
+fn only_requested_renames_keep_a_strict_run_from_starting() {
+    let fs = tree(&["alpha", "beta", "gamma"]);
+    let unrequested = plan(&fs, "old_name,new_name\nalpha,ALPHA\nbeta,\ngamma,gamma\n");
+
+    assert!(StopPolicy::RollBack.rejected(&unrequested).is_empty());
+    assert!(StopPolicy::Stop.rejected(&unrequested).is_empty());
+
+    let plan = plan(&fs, "old_name,new_name\nalpha,ALPHA\nmissing,gone\nbeta,\n");
+    let rows = |stop: StopPolicy| -> Vec<usize> {
+        stop.rejected(&plan)
+            .iter()
+            .map(|rename| rename.row)
+            .collect()
+    };
+    assert_eq!(rows(StopPolicy::RollBack), [3]);
+    assert_eq!(rows(StopPolicy::Stop), [3]);
+    assert!(rows(StopPolicy::Continue).is_empty());
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::journal;
+
+const HEADERS: &str = "timestamp,row,outcome,old_path,new_path,reason,old_path_raw,new_path_raw\n";
+
+/// The `old_path -> new_path` renames undoing `rows` would perform.
+fn undo(rows: &str) -> Vec<String> {
+    let fs = MemoryFs::new();
+    fs.write("/journal.csv", format!("{HEADERS}{rows}"));
+
+    let journaled = journal::read_journal(&fs, Path::new("/journal.csv")).unwrap();
+    journal::undo_plan(journaled.renames)
+        .iter()
+        .map(|rename| format!("{} -> {}", rename.old_name, rename.new_name))
+        .collect()
+}
+
+#[test]
+fn undoes_renames_newest_first() {
+    let rows = "\
+2024-03-09T14:05:00Z,2,renamed,/share/a,/share/x,,,
+2024-03-09T14:05:00Z,3,skipped,/share/b,/share/y,target already exists,,
+2024-03-09T14:05:01Z,4,renamed,/share/c,/share/z,,,
+";
+
+    assert_eq!(undo(rows), ["/share/z -> /share/c", "/share/x -> /share/a"]);
+}
+
+#[test]
+fn leaves_out_renames_that_were_rolled_back() {
+    let rows = "\
+2024-03-09T14:05:00Z,2,renamed,/share/a,/share/x,,,
+2024-03-09T14:05:00Z,3,renamed,/share/b,/share/y,,,
+2024-03-09T14:05:00Z,4,failed,/share/c,/share/z,permission denied,,
+2024-03-09T14:05:01Z,3,rolled_back,/share/y,/share/b,,,
+2024-03-09T14:05:01Z,2,failed,/share/x,/share/a,rollback failed: target already exists,,
+";
+
+    assert_eq!(undo(rows), ["/share/x -> /share/a"]);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use std::path::Path;
+
+use common::{DIRECTORY, names, path, plan_with, run, skip_of, tree};
+use rename_tool::apply::{self, Outcome, StopPolicy};
+use rename_tool::filesystem::{FileSystem, MemoryFs};
+use rename_tool::journal::{self, JournalEntry};
+use rename_tool::plan::{self, CheckOptions, PlannedRename, SkipReason};
+
+fn plan(fs: &MemoryFs, csv: &str, create_parents: bool) -> Vec<PlannedRename> {
+    let options = CheckOptions {
+        create_parents,
+        ..CheckOptions::default()
+    };
+    plan_with(fs, csv, &options)
+}
+
+#[test]
+fn moves_into_an_existing_folder() {
+    let fs = tree(&["inbox/acme", "clients"]);
+    let plan = plan(&fs, "old_name,new_name\ninbox/acme,clients/acme\n", false);
+
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["clients", "clients/acme", "inbox"]);
+}
+
+#[test]
+fn creates_missing_parents() {
+    let fs = tree(&["2023_acme_report"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name\n2023_acme_report,2023/acme/report\n",
+        true,
+    );
+
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["2023", "2023/acme", "2023/acme/report"]);
+}
+
+#[test]
+fn moves_into_a_folder_another_row_renames_into_place() {
+    let fs = tree(&["drafts", "notes"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name\nnotes,archive/notes\ndrafts,archive\n",
+        false,
+    );
+
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["archive", "archive/notes"]);
+}
+
+#[test]
+fn moves_into_a_folder_before_it_is_renamed() {
+    let fs = tree(&["clients", "misc"]);
+    let plan = plan(
+        &fs,
+        "old_name,new_name\nclients,customers\nmisc,clients/misc\n",
+        false,
+    );
+
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["customers", "customers/misc"]);
+}
+
+#[test]
+fn refuses_impossible_moves() {
+    let fs = tree(&["a", "b", "c"]);
+    let plan = plan(&fs, "old_name,new_name\na,b/x\nb,a/y\nc,c/inner\n", true);
+
+    assert!(matches!(skip_of(&plan, 2), Some(SkipReason::CircularMove)));
+    assert!(matches!(skip_of(&plan, 3), Some(SkipReason::CircularMove)));
+    assert!(matches!(skip_of(&plan, 4), Some(SkipReason::IntoItself)));
+}
+
+#[test]
+fn skips_a_move_that_blocks_a_cycle_before_parking_it() {
+    let fs = tree(&["a/one", "b", "c"]);
+    let plan = plan(&fs, "old_name,new_name\na,b\nb,a\nc,b/c\n", false);
+
+    let skipped: Vec<usize> = plan
+        .iter()
+        .filter(|rename| rename.skip.is_some())
+        .map(|rename| rename.row)
+        .collect();
+    assert_eq!(skipped, [4]);
+    assert!(matches!(plan[0].skip, Some(SkipReason::CircularMove)));
+
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["a", "b", "b/one", "c"]);
+}
+
+#[test]
+fn skips_every_row_of_a_cycle_it_cannot_order() {
+    let fs = tree(&["a", "b"]);
+    let plan = plan(&fs, "old_name,new_name\na,b/a\nb,a/b\n", false);
+
+    assert!(
+        plan.iter()
+            .all(|rename| matches!(rename.skip, Some(SkipReason::CircularMove)))
+    );
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["a", "b"]);
+}
+
+#[test]
+fn renames_inside_a_folder_after_renaming_it() {
+    let fs = tree(&["a/sub", "a/keep"]);
+    let plan = plan(&fs, "old_name,new_name\na,A\na/sub,A/SUB\n", false);
+
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+    run(&fs, &plan, StopPolicy::Continue);
+
+    assert_eq!(names(&fs), ["A", "A/SUB", "A/keep"]);
+}
+
+#[test]
+fn rollback_removes_created_parents() {
+    let fs = tree(&["alpha", "beta"]);
+    let mut plan = plan(
+        &fs,
+        "old_name,new_name\nalpha,new/alpha\nbeta,gamma\n",
+        true,
+    );
+
+    // Someone takes the second target after the plan was checked.
+    fs.create_dir_all(path("gamma"));
+    plan.sort_by_key(|rename| rename.row);
+
+    let mut outcomes = Vec::new();
+    apply::execute(&fs, &plan, None, StopPolicy::RollBack, |rename, outcome| {
+        outcomes.push((rename.row, matches!(outcome, Outcome::RolledBack)));
+    })
+    .unwrap();
+
+    assert_eq!(outcomes.last(), Some(&(2, true)));
+    assert_eq!(names(&fs), ["alpha", "beta", "gamma"]);
+}
+
+#[test]
+fn removes_folders_left_empty() {
+    let fs = tree(&["inbox/2023/acme", "inbox/keep", "done"]);
+    let plan = plan(&fs, "old_name,new_name\ninbox/2023/acme,done/acme\n", false);
+    run(&fs, &plan, StopPolicy::Continue);
+
+    let folders = plan::vacated_folders(Path::new(DIRECTORY), &plan);
+    assert_eq!(folders, [path("inbox/2023"), path("inbox")]);
+
+    let removed = apply::remove_empty_folders(&fs, &folders, None).unwrap();
+    assert_eq!(removed, [path("inbox/2023")]);
+    assert_eq!(names(&fs), ["done", "done/acme", "inbox", "inbox/keep"]);
+}
+
+#[test]
+fn keeps_vacated_folders_that_other_rows_renamed_into_place() {
+    let fs = tree(&["a/x", "b", "c/y"]);
+    let plan = plan(&fs, "old_name,new_name\na,b\nb,c\nc,a\nc/y,z/y\n", true);
+
+    let execution = apply::execute(&fs, &plan, None, StopPolicy::Continue, |_, _| {}).unwrap();
+    let renamed = execution.renamed.iter().map(|&index| &plan[index]);
+    let folders = plan::vacated_folders(Path::new(DIRECTORY), renamed);
+    apply::remove_empty_folders(&fs, &folders, None).unwrap();
+
+    // `a` is where `c` went once `y` moved out of it, and `c` is `b`.
+    assert_eq!(names(&fs), ["a", "b", "b/x", "c", "z", "z/y"]);
+}
+
+#[test]
+fn undo_recreates_removed_parents() {
+    let fs = tree(&["done/acme"]);
+    let entries = vec![JournalEntry {
+        line: 2,
+        old_path: path("inbox/2023/acme"),
+        new_path: path("done/acme"),
+        rollback: false,
+    }];
+
+    let mut undo = journal::undo_plan(entries);
+    plan::check_sequential(&fs, &mut undo);
+    run(&fs, &undo, StopPolicy::Continue);
+
+    assert!(fs.is_dir(&path("inbox/2023/acme")));
+}
+
+#[test]
+fn rows_without_a_rename_do_not_stop_a_run() {
+    for stop in [StopPolicy::Stop, StopPolicy::RollBack] {
+        let fs = tree(&["a", "b", "c"]);
+        let plan = plan(&fs, "old_name,new_name\na,A\nb,\nc,c\n", false);
+
+        let execution = apply::execute(&fs, &plan, None, stop, |_, _| {}).unwrap();
+
+        assert!(execution.stopped_at.is_none());
+        assert_eq!(names(&fs), ["A", "b", "c"]);
+    }
+}
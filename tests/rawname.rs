@@ -0,0 +1,91 @@
+#![cfg(unix)]
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use rename_tool::apply::{self, StopPolicy};
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::{FileSystem, MemoryFs};
+use rename_tool::import::{self, CsvOptions};
+use rename_tool::plan::{self, CheckOptions, SkipReason};
+use rename_tool::rawname;
+
+fn latin1(name: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(name))
+}
+
+#[test]
+fn valid_names_need_no_escaping() {
+    assert_eq!(rawname::encode(Path::new("café/100%")), None);
+}
+
+#[test]
+fn escapes_invalid_bytes_and_percent_signs() {
+    let name = latin1(b"caf\xe9/100%");
+
+    assert_eq!(rawname::encode(&name).as_deref(), Some("caf%E9/100%25"));
+    assert_eq!(rawname::decode("caf%E9/100%25"), Some(name));
+}
+
+#[test]
+fn keeps_absolute_paths_absolute() {
+    let path = latin1(b"/share/caf\xe9");
+
+    assert_eq!(rawname::decode(&rawname::escape(&path)), Some(path));
+}
+
+#[test]
+fn rejects_malformed_escapes() {
+    assert_eq!(rawname::decode("caf%E"), None);
+    assert_eq!(rawname::decode("caf%G9"), None);
+}
+
+#[test]
+fn export_and_import_find_the_exact_entry() {
+    let fs = MemoryFs::new();
+    fs.create_dir_all(Path::new("/share").join(latin1(b"caf\xe9/2023")));
+
+    let options = ExportOptions {
+        recursive: true,
+        ..ExportOptions::default()
+    };
+    let listing = export::list_entries(&fs, Path::new("/share"), &options).unwrap();
+    let raw_names: Vec<Option<&str>> = listing
+        .entries
+        .iter()
+        .map(|entry| entry.raw_name.as_deref())
+        .collect();
+    assert_eq!(raw_names, [Some("caf%E9"), Some("caf%E9/2023")]);
+    assert_eq!(listing.entries[1].name, "caf\u{FFFD}/2023");
+
+    fs.write(
+        "/plan.csv",
+        "old_name,new_name,old_name_raw\n\
+         caf\u{FFFD}/2023,caf\u{FFFD}/2024,caf%E9/2023\n\
+         caf\u{FFFD},cafe,caf%E9\n\
+         caf\u{FFFD},other,caf%ZZ\n",
+    );
+    let mut plan = import::read_plan(
+        &fs,
+        Path::new("/share"),
+        Path::new("/plan.csv"),
+        &CsvOptions::default(),
+    )
+    .unwrap()
+    .plan;
+    assert!(matches!(plan[2].skip, Some(SkipReason::InvalidRawName(_))));
+
+    plan.truncate(2);
+    plan::check(
+        &fs,
+        Path::new("/share"),
+        &mut plan,
+        &CheckOptions::default(),
+    );
+    assert!(plan.iter().all(|rename| rename.skip.is_none()));
+
+    apply::execute(&fs, &plan, None, StopPolicy::Continue, |_, _| {}).unwrap();
+
+    assert!(fs.is_dir(Path::new("/share/cafe/2024")));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::path::Path;
+
+use common::{path, plan, tree};
+use rename_tool::apply::{self, StopPolicy};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::plan::PlannedRename;
+use rename_tool::report::{Report, ReportFormat};
+
+/// Runs `plan` and records every outcome in a report.
+fn run(fs: &MemoryFs, plan: &[PlannedRename], stop: StopPolicy) -> Report {
+    let mut report = Report::new(Path::new("/plan.csv"), false);
+    apply::execute(fs, plan, None, stop, |rename, outcome| {
+        report.record(rename, outcome)
+    })
+    .unwrap();
+    report
+}
+
+#[test]
+fn escapes_json_strings() {
+    let fs = tree(&[]);
+    let name = "say \"hi\"\\\n\tnow\u{1}";
+    fs.create_dir_all(path(name));
+    let plan = plan(
+        &fs,
+        "old_name,new_name\n\"say \"\"hi\"\"\\\n\tnow\u{1}\",done\n",
+    );
+
+    let json = run(&fs, &plan, StopPolicy::Continue).render(ReportFormat::Json);
+
+    assert!(
+        json.contains(r#""old_path": "/share/say \"hi\"\\\n\tnow\u0001""#),
+        "{json}"
+    );
+    assert!(json.contains(r#""source": "/plan.csv""#));
+}
+
+#[test]
+fn counts_a_row_split_through_a_temporary_name_once() {
+    let fs = tree(&["a", "b"]);
+    let plan = plan(&fs, "old_name,new_name\na,b\nb,a\n");
+
+    let report = run(&fs, &plan, StopPolicy::Continue);
+    let json = report.render(ReportFormat::Json);
+    let counts = report.counts();
+
+    assert_eq!(json.matches("\"outcome\": \"renamed\"").count(), 3);
+    assert!(json.contains(".rename_tool_tmp_2"));
+    assert_eq!((counts.rows, counts.renamed), (2, 2));
+}
+
+#[test]
+fn counts_rolled_back_rows_by_their_last_outcome() {
+    let fs = tree(&["a", "b"]);
+    let plan = plan(&fs, "old_name,new_name\na,x\nb,y\nc,\n");
+    // Claimed after the checks, so the second rename is skipped as it runs.
+    fs.create_dir_all(path("y"));
+
+    let report = run(&fs, &plan, StopPolicy::RollBack);
+    let counts = report.counts();
+
+    assert_eq!(counts.rows, 3);
+    assert_eq!(counts.rolled_back, 1);
+    assert_eq!(counts.skipped, 1);
+    assert_eq!(counts.unchanged, 1);
+    assert_eq!(counts.renamed, 0);
+    assert!(
+        report
+            .render(ReportFormat::Json)
+            .contains("\"counts\": {\"rows\": 3, \"planned\": 0, \"renamed\": 0, \"unchanged\": 1, \"skipped\": 1, \"failed\": 0, \"rolled_back\": 1}")
+    );
+}
+
+#[test]
+fn counts_planned_rows_of_a_dry_run() {
+    let fs = tree(&["a"]);
+    let plan = plan(&fs, "old_name,new_name\na,x\nmissing,y\n");
+    let mut report = Report::new(Path::new("/plan.csv"), true);
+
+    for rename in &plan {
+        report.record_planned(rename);
+    }
+    let counts = report.counts();
+
+    assert_eq!((counts.planned, counts.skipped), (1, 1));
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::path::Path;
+
+use common::{DIRECTORY, read_plan};
+use rename_tool::entry::Context;
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::plan::{self, CheckOptions, PlannedRename};
+use rename_tool::review;
+
+fn tree() -> MemoryFs {
+    let fs = common::tree(&["alpha", "beta", "gamma", "delta"]);
+    fs.write("/share/alpha/notes.txt", "hello");
+    fs.write("/share/alpha/sub/more.txt", "world!");
+    fs
+}
+
+/// Reviews a plan renaming every folder to its uppercase name, answering
+/// with `answers`, and returns the rows and new names left in the plan.
+fn review(fs: &MemoryFs, answers: &str) -> (Vec<(usize, String)>, String) {
+    review_csv(
+        fs,
+        "old_name,new_name\nalpha,ALPHA\nbeta,BETA\ngamma,GAMMA\ndelta,DELTA\n",
+        answers,
+    )
+}
+
+/// Reviews the plan in `csv` the way `review` does.
+fn review_csv(fs: &MemoryFs, csv: &str, answers: &str) -> (Vec<(usize, String)>, String) {
+    let mut plan = read_plan(fs, csv);
+    let options = CheckOptions::default();
+
+    let mut output = Vec::new();
+    review::review(
+        fs,
+        Path::new(DIRECTORY),
+        &mut plan,
+        &mut answers.as_bytes(),
+        &mut output,
+        |plan: &mut [PlannedRename]| {
+            plan::check_rows(fs, Path::new(DIRECTORY), plan, &options);
+        },
+    )
+    .unwrap();
+
+    let kept = plan
+        .iter()
+        .filter(|rename| rename.skip.is_none())
+        .map(|rename| (rename.row, rename.new_name.clone()))
+        .collect();
+    (kept, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn accepts_skips_and_quits() {
+    let (kept, output) = review(&tree(), "y\nn\nwhat\ny\nq\n");
+
+    assert_eq!(kept, [(2, "ALPHA".to_string()), (4, "GAMMA".to_string())]);
+    assert!(output.contains("Please answer y, n, e, a or q."));
+}
+
+#[test]
+fn accepts_all_remaining() {
+    let (kept, _) = review(&tree(), "n\na\n");
+
+    assert_eq!(
+        kept.iter().map(|(row, _)| *row).collect::<Vec<_>>(),
+        [3, 4, 5]
+    );
+}
+
+#[test]
+fn the_end_of_input_declines_the_rest() {
+    let (kept, _) = review(&tree(), "y\n");
+
+    assert_eq!(kept, [(2, "ALPHA".to_string())]);
+}
+
+#[test]
+fn edits_a_name_and_asks_again_when_it_fails_its_checks() {
+    let (kept, output) = review(&tree(), "e\nalpha/inside\ne\nomega\ny\nq\n");
+
+    assert!(output.contains("will be skipped: cannot move a folder into itself"));
+    assert_eq!(kept, [(2, "omega".to_string())]);
+}
+
+#[test]
+fn an_edit_frees_the_rows_that_clashed_with_the_old_name() {
+    let (kept, output) = review_csv(
+        &tree(),
+        "old_name,new_name\nalpha,omega\nbeta,omega\n",
+        "e\nzeta\ny\ny\n",
+    );
+
+    assert!(output.contains("Row 3: beta -> omega"));
+    assert_eq!(kept, [(2, "zeta".to_string()), (3, "omega".to_string())]);
+}
+
+#[test]
+fn declined_rows_do_not_hold_on_to_their_new_name() {
+    let (kept, _) = review_csv(
+        &tree(),
+        "old_name,new_name\nalpha,omega\nbeta,zeta\n",
+        "n\ne\nomega\ny\n",
+    );
+
+    assert_eq!(kept, [(3, "omega".to_string())]);
+}
+
+#[test]
+fn rows_skipped_while_reading_stay_skipped() {
+    let fs = tree();
+    let (kept, _) = review_csv(
+        &fs,
+        "old_name,new_name\nalpha,\nbeta,omega\n",
+        "e\nzeta\ny\n",
+    );
+
+    assert_eq!(kept, [(3, "zeta".to_string())]);
+}
+
+#[test]
+fn shows_size_item_count_and_modification_time() {
+    let fs = tree();
+    let context = Context::of(&fs, Path::new("/share/alpha")).unwrap();
+
+    assert_eq!(context.items, Some(3));
+    assert_eq!(context.size, 11);
+    assert!(
+        context
+            .to_string()
+            .starts_with("dir, 3 item(s), 11 B, modified 1970-01-01T")
+    );
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use rename_tool::columns::{Columns, Value};
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::import::{self, CsvOptions};
+use rename_tool::spreadsheet::{self, Format};
+
+const DIRECTORY: &str = "/share";
+
+/// Names a spreadsheet opening a CSV would turn into numbers, dates or
+/// formulas, or whose spaces an ODS paragraph would collapse.
+const NAMES: [&str; 5] = ["007", "1e5", "2024-03-09", "=A1 & <b>", "a  b"];
+
+#[test]
+fn chooses_the_format_by_extension() {
+    assert_eq!(Format::of(Path::new("plan.xlsx")), Some(Format::Xlsx));
+    assert_eq!(Format::of(Path::new("Plan.ODS")), Some(Format::Ods));
+    assert_eq!(Format::of(Path::new("plan.csv")), None);
+    assert_eq!(Format::of(Path::new("xlsx")), None);
+}
+
+#[test]
+fn round_trips_names_as_text() {
+    for (format, path) in [(Format::Xlsx, "/plan.xlsx"), (Format::Ods, "/plan.ods")] {
+        let fs = MemoryFs::new();
+        for name in NAMES {
+            fs.create_dir_all(Path::new(DIRECTORY).join(name));
+        }
+
+        let mut listing =
+            export::list_entries(&fs, Path::new(DIRECTORY), &ExportOptions::default()).unwrap();
+        for entry in &mut listing.entries {
+            entry.new_name = format!("0{}", entry.name);
+        }
+        let rows = export::rows(&fs, &listing.entries, &listing.stamp, &Columns::default());
+        fs.write(path, spreadsheet::write(format, &rows).unwrap());
+
+        let plan = import::read_plan(
+            &fs,
+            Path::new(DIRECTORY),
+            Path::new(path),
+            &CsvOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            plan.stamp.map(|stamp| stamp.to_string()),
+            Some(listing.stamp.to_string())
+        );
+        let names: Vec<_> = plan
+            .plan
+            .iter()
+            .map(|rename| {
+                assert!(rename.skip.is_none(), "{:?}", rename.old_name);
+                assert!(rename.fingerprint.is_some());
+                (
+                    rename.row,
+                    rename.old_name.as_str(),
+                    rename.new_name.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (2, "007", "0007"),
+                (3, "1e5", "01e5"),
+                (4, "2024-03-09", "02024-03-09"),
+                (5, "=A1 & <b>", "0=A1 & <b>"),
+                (6, "a  b", "0a  b"),
+            ]
+        );
+    }
+}
+
+#[test]
+fn keeps_metadata_columns_typed() {
+    let time = UNIX_EPOCH + Duration::from_secs(1_709_993_100);
+    let rows = vec![
+        vec![
+            Value::Text("old_name".to_string()),
+            Value::Text("size".to_string()),
+            Value::Text("modified".to_string()),
+        ],
+        vec![
+            Value::Text("a\tb".to_string()),
+            Value::Number(42),
+            Value::Time(time),
+        ],
+        vec![Value::Empty, Value::Empty, Value::Empty],
+        vec![Value::Text("c".to_string()), Value::Empty, Value::Empty],
+    ];
+
+    for format in [Format::Xlsx, Format::Ods] {
+        let read = spreadsheet::read(spreadsheet::write(format, &rows).unwrap()).unwrap();
+
+        assert_eq!(
+            read,
+            [
+                (1, vec!["old_name", "size", "modified"]),
+                (2, vec!["a\tb", "42", "2024-03-09 14:05:00"]),
+                (4, vec!["c", "", ""]),
+            ]
+            .map(|(row, cells)| (row, cells.into_iter().map(String::from).collect())),
+            "{format:?}"
+        );
+    }
+}
+
+#[test]
+fn reads_chosen_columns_without_headers() {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/007");
+    let rows = vec![vec![
+        Value::Empty,
+        Value::Text("007".to_string()),
+        Value::Text("008".to_string()),
+    ]];
+    fs.write("/plan.ods", spreadsheet::write(Format::Ods, &rows).unwrap());
+
+    let options = CsvOptions {
+        old_column: Some("2".to_string()),
+        new_column: Some("3".to_string()),
+        no_header: true,
+        ..CsvOptions::default()
+    };
+    let plan = import::read_plan(&fs, Path::new(DIRECTORY), Path::new("/plan.ods"), &options)
+        .unwrap()
+        .plan;
+
+    assert_eq!(plan.len(), 1);
+    assert_eq!(
+        (
+            plan[0].row,
+            plan[0].old_name.as_str(),
+            plan[0].new_name.as_str()
+        ),
+        (1, "007", "008")
+    );
+}
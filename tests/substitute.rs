@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use rename_tool::Error;
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::substitute::{self, RegexOptions};
+
+fn tree() -> MemoryFs {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/Photos 2023/photos 2022");
+    fs.create_dir_all("/share/photos-photos");
+    fs.create_dir_all("/share/scans");
+    fs
+}
+
+/// Renames the folders of `tree`, at any depth, and returns the old and new
+/// names of the entries kept.
+fn rename(pattern: &str, replacement: &str, options: &RegexOptions) -> Vec<(String, String)> {
+    let listing = ExportOptions {
+        recursive: true,
+        ..ExportOptions::default()
+    };
+    let listed = export::list_entries(&tree(), Path::new("/share"), &listing)
+        .unwrap()
+        .entries;
+
+    substitute::rename_matching(listed, pattern, replacement, options)
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.name, entry.new_name))
+        .collect()
+}
+
+fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(old, new)| (old.to_string(), new.to_string()))
+        .collect()
+}
+
+#[test]
+fn drops_entries_the_pattern_leaves_unchanged() {
+    assert_eq!(
+        rename("photos", "pictures", &RegexOptions::default()),
+        pairs(&[
+            ("Photos 2023/photos 2022", "Photos 2023/pictures 2022"),
+            ("photos-photos", "pictures-photos"),
+        ])
+    );
+    assert_eq!(rename("scans", "scans", &RegexOptions::default()), []);
+}
+
+#[test]
+fn rewrites_only_the_last_part_of_nested_names() {
+    let options = RegexOptions {
+        ignore_case: true,
+        ..RegexOptions::default()
+    };
+
+    assert_eq!(
+        rename("^photos (\\d+)$", "$1", &options),
+        pairs(&[
+            ("Photos 2023", "2023"),
+            ("Photos 2023/photos 2022", "Photos 2023/2022"),
+        ])
+    );
+}
+
+#[test]
+fn expands_numbered_and_named_groups() {
+    assert_eq!(
+        rename(
+            "^(?<kind>\\w+) (\\d+)$",
+            "${2}_${kind}",
+            &RegexOptions::default()
+        ),
+        pairs(&[
+            ("Photos 2023", "2023_Photos"),
+            ("Photos 2023/photos 2022", "Photos 2023/2022_photos"),
+        ])
+    );
+}
+
+#[test]
+fn replaces_every_match_with_replace_all() {
+    let options = RegexOptions {
+        replace_all: true,
+        ..RegexOptions::default()
+    };
+
+    assert_eq!(
+        rename("photos", "pics", &options),
+        pairs(&[
+            ("Photos 2023/photos 2022", "Photos 2023/pics 2022"),
+            ("photos-photos", "pics-pics"),
+        ])
+    );
+}
+
+#[test]
+fn only_considers_names_the_filter_matches() {
+    let options = RegexOptions {
+        ignore_case: true,
+        filter: Some("^photos \\d+$".to_string()),
+        ..RegexOptions::default()
+    };
+
+    // The filter sees the whole relative path, so the nested folder is left
+    // out even though its own name matches.
+    assert_eq!(
+        rename("photos", "pictures", &options),
+        pairs(&[("Photos 2023", "pictures 2023")])
+    );
+}
+
+#[test]
+fn rejects_invalid_patterns() {
+    let options = RegexOptions {
+        filter: Some("(".to_string()),
+        ..RegexOptions::default()
+    };
+
+    for (pattern, options) in [("[", &RegexOptions::default()), ("a", &options)] {
+        let result = substitute::rename_matching(Vec::new(), pattern, "", options);
+        assert!(matches!(result, Err(Error::InvalidPattern { .. })));
+    }
+}
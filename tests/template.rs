@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use rename_tool::Error;
+use rename_tool::entry::TypeFilter;
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::template::Template;
+
+/// `/share/photos` is created at second 2 of the tree's clock, and the file
+/// in it at second 3, which is also when the folder was last modified.
+fn tree() -> MemoryFs {
+    let fs = MemoryFs::new();
+    fs.create_dir_all("/share/photos");
+    fs.write("/share/photos/holiday_in-ROME.jpg", "");
+    fs
+}
+
+fn render(fs: &MemoryFs, template: &str, path: &str) -> Result<String, String> {
+    template
+        .parse::<Template>()
+        .unwrap()
+        .render(fs, Path::new(path), 7, "photos")
+}
+
+#[test]
+fn rejects_malformed_templates() {
+    for (template, message) in [
+        ("{name", "unclosed placeholder {name"),
+        ("name}", "unmatched }, write }} for a literal brace"),
+        ("{size}", "unknown placeholder {size}"),
+        ("{counter:x}", "invalid counter width \"x\""),
+        ("{stem:2}", "{stem} does not take an argument"),
+        ("{name|shout}", "unknown transform |shout"),
+    ] {
+        match template.parse::<Template>() {
+            Err(Error::InvalidTemplate {
+                template: rejected,
+                message: reason,
+            }) => {
+                assert_eq!(rejected, template);
+                assert_eq!(reason, message, "{template}");
+            }
+            _ => panic!("{template} was accepted"),
+        }
+    }
+}
+
+#[test]
+fn fills_in_the_name_fields_and_literal_braces() {
+    let fs = tree();
+
+    assert_eq!(
+        render(
+            &fs,
+            "{{{parent}}} {stem}.{ext} {name}",
+            "/share/photos/holiday_in-ROME.jpg"
+        ),
+        Ok("{photos} holiday_in-ROME.jpg holiday_in-ROME.jpg".to_string())
+    );
+    assert_eq!(
+        render(&fs, "{stem}|{ext}", "/share/photos"),
+        Ok("photos|".to_string())
+    );
+}
+
+#[test]
+fn pads_the_counter_to_its_width() {
+    let fs = tree();
+
+    assert_eq!(
+        render(&fs, "{counter:3}_{name}", "/share/photos"),
+        Ok("007_photos".to_string())
+    );
+    assert_eq!(
+        render(&fs, "{counter}-{counter:0}", "/share/photos"),
+        Ok("7-7".to_string())
+    );
+}
+
+#[test]
+fn applies_case_transforms_in_order() {
+    let fs = tree();
+    let path = "/share/photos/holiday_in-ROME.jpg";
+
+    assert_eq!(
+        render(&fs, "{stem|title}", path),
+        Ok("Holiday_In-Rome".to_string())
+    );
+    assert_eq!(
+        render(&fs, "{stem|title|upper} {ext|upper|lower}", path),
+        Ok("HOLIDAY_IN-ROME jpg".to_string())
+    );
+}
+
+#[test]
+fn formats_the_dates_of_the_entry() {
+    let fs = tree();
+
+    assert_eq!(
+        render(&fs, "{modified}", "/share/photos"),
+        Ok("1970-01-01".to_string())
+    );
+    assert_eq!(
+        render(&fs, "{created:%H%M%S}-{modified:%H%M%S}", "/share/photos"),
+        Ok("000002-000003".to_string())
+    );
+}
+
+#[test]
+fn reports_dates_of_missing_entries_as_unavailable() {
+    let fs = tree();
+
+    assert!(
+        render(&fs, "{modified}", "/share/missing")
+            .unwrap_err()
+            .starts_with("modification time unavailable")
+    );
+    assert_eq!(
+        render(&fs, "{name}", "/share/missing"),
+        Ok("missing".to_string())
+    );
+}
+
+#[test]
+fn keeps_nested_entries_in_their_folder() {
+    let fs = tree();
+    let options = ExportOptions {
+        recursive: true,
+        type_filter: TypeFilter::All,
+        ..ExportOptions::default()
+    };
+    let mut listing = export::list_entries(&fs, Path::new("/share"), &options).unwrap();
+    let template = "{counter:2}_{parent|upper}".parse::<Template>().unwrap();
+
+    let failures =
+        export::apply_template(&fs, &template, Path::new("/share"), &mut listing.entries);
+
+    assert!(failures.is_empty());
+    assert_eq!(
+        listing
+            .entries
+            .iter()
+            .map(|entry| entry.new_name.as_str())
+            .collect::<Vec<_>>(),
+        ["01_SHARE", "photos/02_PHOTOS"]
+    );
+}
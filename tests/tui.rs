@@ -0,0 +1,190 @@
+mod common;
+
+use std::path::Path;
+
+use common::DIRECTORY;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use rename_tool::export::{self, ExportOptions};
+use rename_tool::filesystem::MemoryFs;
+use rename_tool::plan::{self, CheckOptions, PlannedRename};
+use rename_tool::tui::{Action, Session, SortKey, Status};
+use rename_tool::validate::Profile;
+
+fn tree() -> MemoryFs {
+    common::tree(&["alpha", "beta", "gamma"])
+}
+
+/// The checks `rename_tool tui` runs after every change, on `portable`.
+fn check(fs: &MemoryFs) -> impl FnMut(&mut [PlannedRename]) + '_ {
+    let options = CheckOptions {
+        profile: Profile::Portable,
+        ..CheckOptions::default()
+    };
+    move |plan: &mut [PlannedRename]| {
+        plan::check_rows(fs, Path::new(DIRECTORY), plan, &options);
+    }
+}
+
+fn session(fs: &MemoryFs) -> Session {
+    let listed = export::list_entries(fs, Path::new(DIRECTORY), &ExportOptions::default())
+        .unwrap()
+        .entries;
+    Session::new(DIRECTORY.into(), listed, &mut check(fs))
+}
+
+/// Presses each key in turn; characters in `{...}` name a special key.
+fn press(session: &mut Session, fs: &MemoryFs, keys: &str) -> Action {
+    let mut check = check(fs);
+    let mut action = Action::Continue;
+    let mut chars = keys.chars();
+
+    while let Some(c) = chars.next() {
+        let code = if c == '{' {
+            let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+            match name.as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "down" => KeyCode::Down,
+                "backspace" => KeyCode::Backspace,
+                _ => panic!("unknown key {name}"),
+            }
+        } else {
+            KeyCode::Char(c)
+        };
+        action = session.handle_key(KeyEvent::new(code, KeyModifiers::NONE), &mut check);
+    }
+
+    action
+}
+
+fn shown(session: &Session) -> Vec<(String, String, Status)> {
+    session
+        .visible()
+        .map(|(entry, status)| (entry.name.clone(), entry.new_name.clone(), status.clone()))
+        .collect()
+}
+
+#[test]
+fn edits_a_name_in_place() {
+    let fs = tree();
+    let mut session = session(&fs);
+
+    press(
+        &mut session,
+        &fs,
+        "{down}e{backspace}{backspace}{backspace}{backspace}ta{enter}",
+    );
+
+    assert_eq!(
+        shown(&session)[1],
+        (
+            "beta".to_string(),
+            "ta".to_string(),
+            Status::Renamed("ta".to_string())
+        )
+    );
+    assert_eq!(session.renames().len(), 1);
+    assert_eq!(session.renames()[0].0, 2);
+}
+
+#[test]
+fn highlights_conflicts_as_they_are_typed() {
+    let fs = tree();
+    let mut session = session(&fs);
+
+    // alpha -> gamma, which stays; beta -> a:b, which is not portable.
+    press(
+        &mut session,
+        &fs,
+        "e{backspace}{backspace}{backspace}{backspace}{backspace}gamma{enter}",
+    );
+    press(
+        &mut session,
+        &fs,
+        "{down}e{backspace}{backspace}{backspace}{backspace}a:b{enter}",
+    );
+
+    let rows = shown(&session);
+    assert!(matches!(&rows[0].2, Status::Problem(reason) if reason.contains("exists")));
+    assert!(matches!(&rows[1].2, Status::Problem(reason) if reason.contains("':'")));
+
+    // Renaming gamma away frees its name.
+    press(
+        &mut session,
+        &fs,
+        "{down}e{backspace}{backspace}{backspace}{backspace}{backspace}delta{enter}",
+    );
+    assert_eq!(shown(&session)[0].2, Status::Renamed("gamma".to_string()));
+}
+
+#[test]
+fn replaces_in_the_filtered_names() {
+    let fs = tree();
+    let mut session = session(&fs);
+
+    press(&mut session, &fs, "/a{enter}");
+    assert_eq!(shown(&session).len(), 3);
+    press(&mut session, &fs, "/{backspace}mm{enter}");
+    assert_eq!(shown(&session).len(), 1);
+
+    press(&mut session, &fs, "r(m+){enter}[$1]{enter}");
+    assert_eq!(session.message(), Some("Replaced \"(m+)\" in 1 name(s)."));
+
+    // Clearing the filter shows every entry again, and only gamma changed.
+    press(&mut session, &fs, "{esc}");
+    let renamed: Vec<_> = shown(&session)
+        .into_iter()
+        .filter(|(_, new_name, _)| !new_name.is_empty())
+        .map(|(name, new_name, _)| (name, new_name))
+        .collect();
+    assert_eq!(renamed, [("gamma".to_string(), "ga[mm]a".to_string())]);
+}
+
+#[test]
+fn reports_invalid_patterns() {
+    let fs = tree();
+    let mut session = session(&fs);
+
+    press(&mut session, &fs, "r({enter}x{enter}");
+
+    assert!(session.message().unwrap().starts_with("Invalid pattern"));
+    assert!(session.renames().is_empty());
+}
+
+#[test]
+fn sorts_by_status_with_problems_first() {
+    let fs = tree();
+    let mut session = session(&fs);
+
+    press(
+        &mut session,
+        &fs,
+        "{down}{down}e{backspace}{backspace}{backspace}{backspace}{backspace}alpha{enter}",
+    );
+    press(&mut session, &fs, "ssss");
+    assert_eq!(session.sort(), SortKey::Name);
+    press(&mut session, &fs, "sss");
+    assert_eq!(session.sort(), SortKey::Status);
+
+    assert_eq!(shown(&session)[0].0, "gamma");
+    assert_eq!(session.selected().unwrap().name, "gamma");
+}
+
+#[test]
+fn applies_and_quits_after_confirmation() {
+    let fs = tree();
+    let mut session = session(&fs);
+
+    assert_eq!(press(&mut session, &fs, "a"), Action::Continue);
+    assert_eq!(session.message(), Some("No new names to apply."));
+
+    press(&mut session, &fs, "e{backspace}{enter}");
+    assert_eq!(press(&mut session, &fs, "an"), Action::Continue);
+    assert_eq!(press(&mut session, &fs, "q"), Action::Continue);
+    assert_eq!(press(&mut session, &fs, "y"), Action::Quit);
+    assert_eq!(press(&mut session, &fs, "ay"), Action::Apply);
+
+    // Clearing the only new name lets q quit right away.
+    press(&mut session, &fs, "x");
+    assert_eq!(press(&mut session, &fs, "q"), Action::Quit);
+}
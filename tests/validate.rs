@@ -0,0 +1,149 @@
+mod common;
+
+use std::path::Path;
+
+use common::{DIRECTORY, read_plan, tree};
+use rename_tool::plan::{self, CheckOptions, PlannedRename, SkipReason};
+use rename_tool::validate::{self, Problem, Profile};
+
+const PROFILES: [Profile; 4] = [
+    Profile::Posix,
+    Profile::Windows,
+    Profile::Macos,
+    Profile::Portable,
+];
+
+fn plan(csv: &str, profile: Profile, sanitize: bool) -> Vec<PlannedRename> {
+    let fs = tree(&["alpha", "beta"]);
+    let mut plan = read_plan(&fs, csv);
+    // `..` would otherwise be refused as the directory itself, before its
+    // name is checked.
+    let options = CheckOptions {
+        allow_move_outside: true,
+        profile,
+        sanitize,
+        ..CheckOptions::default()
+    };
+    plan::check_rows(&fs, Path::new(DIRECTORY), &mut plan, &options);
+    plan
+}
+
+#[test]
+fn every_profile_rejects_dot_names_nul_and_long_names() {
+    let long = "a".repeat(256);
+
+    for profile in PROFILES {
+        assert!(validate::check_name("report 2024", profile).is_none());
+        assert!(matches!(
+            validate::check_name("..", profile),
+            Some(Problem::Reserved(_))
+        ));
+        assert!(matches!(
+            validate::check_name("a\0b", profile),
+            Some(Problem::Character('\0'))
+        ));
+        assert!(matches!(
+            validate::check_name(&long, profile),
+            Some(Problem::TooLong { length: 256, .. })
+        ));
+    }
+}
+
+#[test]
+fn windows_rules_apply_to_windows_and_portable() {
+    for name in ["CON", "nul.txt", "com1", "what?", "notes.", "trailing "] {
+        assert!(
+            validate::check_name(name, Profile::Posix).is_none(),
+            "{name}"
+        );
+        assert!(
+            validate::check_name(name, Profile::Windows).is_some(),
+            "{name}"
+        );
+        assert!(
+            validate::check_name(name, Profile::Portable).is_some(),
+            "{name}"
+        );
+    }
+    assert!(validate::check_name("console", Profile::Windows).is_none());
+    assert!(validate::check_name("a:b", Profile::Macos).is_some());
+}
+
+#[test]
+fn windows_counts_utf16_code_units() {
+    // 128 two-byte characters are 256 bytes but 128 UTF-16 code units.
+    let name = "é".repeat(128);
+
+    assert!(validate::check_name(&name, Profile::Windows).is_none());
+    assert!(validate::check_name(&name, Profile::Posix).is_some());
+}
+
+#[test]
+fn sanitized_names_are_valid() {
+    let long = format!("{}.pdf", "é".repeat(200));
+
+    for profile in PROFILES {
+        for name in ["a:b", "CON.txt", "what?. ", "..", "a/b", long.as_str()] {
+            let sanitized = validate::sanitize(name, profile);
+            assert!(
+                validate::check_name(&sanitized, profile).is_none(),
+                "{name} -> {sanitized} on {profile}"
+            );
+        }
+    }
+    assert_eq!(validate::sanitize("a:b?", Profile::Windows), "a_b_");
+    assert_eq!(validate::sanitize("con.txt", Profile::Windows), "con_.txt");
+    assert!(validate::sanitize(&long, Profile::Posix).ends_with(".pdf"));
+}
+
+#[test]
+fn skips_rows_with_invalid_new_names() {
+    let plan = plan(
+        "old_name,new_name\nalpha,..\nbeta,aux\n",
+        Profile::Windows,
+        false,
+    );
+
+    assert!(plan.iter().all(|rename| matches!(
+        rename.skip,
+        Some(SkipReason::InvalidName(Profile::Windows, _))
+    )));
+}
+
+#[test]
+fn sanitizes_invalid_new_names() {
+    let plan = plan(
+        "old_name,new_name\nalpha,..\nbeta,q1: plans?\n",
+        Profile::Windows,
+        true,
+    );
+
+    assert!(matches!(plan[0].skip, Some(SkipReason::InvalidName(..))));
+    assert!(plan[1].skip.is_none());
+    assert_eq!(plan[1].new_name, "q1_ plans_");
+    assert_eq!(plan[1].new_path, Path::new("/share/q1_ plans_"));
+}
+
+#[test]
+fn checks_only_the_folders_a_move_creates() {
+    let fs = tree(&["alpha", "beta", "what?"]);
+    let mut plan = read_plan(
+        &fs,
+        "old_name,new_name\nalpha,what?/alpha\nbeta,new?/beta\n",
+    );
+    let options = CheckOptions {
+        profile: Profile::Windows,
+        create_parents: true,
+        ..CheckOptions::default()
+    };
+    plan::check_rows(&fs, Path::new(DIRECTORY), &mut plan, &options);
+
+    assert!(plan[0].skip.is_none());
+    assert!(matches!(
+        plan[1].skip,
+        Some(SkipReason::InvalidName(
+            Profile::Windows,
+            Problem::Character('?')
+        ))
+    ));
+}